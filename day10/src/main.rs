@@ -1,7 +1,10 @@
 use clap::Parser;
 use std::{
+    collections::HashMap,
+    fmt,
     fs::File,
-    io::{BufRead, BufReader},
+    io::{self, BufRead, BufReader, Write},
+    str::FromStr,
 };
 
 #[derive(Parser, Debug)]
@@ -9,103 +12,819 @@ use std::{
 struct Args {
     #[arg(long)]
     data_file: String,
+    /// Print the cycle, instruction and X register for every cycle
+    #[arg(long)]
+    trace: bool,
+    /// Stop tracing, or continuing in the debugger, after this many cycles as programs with jumps
+    /// may never finish
+    #[arg(long, default_value_t = 100_000)]
+    max_cycles: usize,
+    /// Step through the program in an interactive debugger before running it
+    #[arg(long)]
+    debug: bool,
+    /// The first cycle to sample the signal strength during
+    #[arg(long, default_value_t = 20)]
+    sample_start: usize,
+    /// Number of cycles between each sample
+    #[arg(long, default_value_t = 40)]
+    sample_interval: usize,
+    /// The last cycle which may be sampled
+    #[arg(long, default_value_t = 220)]
+    sample_end: usize,
+    /// Comma separated list of cycles to sample, used instead of the start, interval and end
+    #[arg(long)]
+    sample_cycles: Option<String>,
+    /// Number of pixels in each row of the CRT
+    #[arg(long, default_value_t = 40)]
+    crt_width: usize,
+    /// Number of rows on the CRT
+    #[arg(long, default_value_t = 6)]
+    crt_height: usize,
+}
+
+// Registers are referred to by name in programs, the CRT sprite position is always held in x
+const REGISTERS: [&str; 4] = ["x", "y", "z", "w"];
+const X: usize = 0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Opcode {
+    Noop,
+    Addx,
+    Set,
+    Add,
+    Mul,
+    Jmp,
+    Jnz,
+}
+
+impl Opcode {
+    // The mnemonic and number of cycles each instruction takes to complete. Its effect is applied
+    // on the last cycle.
+    fn definition(self) -> (&'static str, usize) {
+        match self {
+            Opcode::Noop => ("noop", 1),
+            Opcode::Addx => ("addx", 2),
+            Opcode::Set => ("set", 1),
+            Opcode::Add => ("add", 2),
+            Opcode::Mul => ("mul", 3),
+            Opcode::Jmp => ("jmp", 1),
+            Opcode::Jnz => ("jnz", 2),
+        }
+    }
+}
+
+// Either a register or a literal value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operand {
+    Register(usize),
+    Value(i32),
+}
+
+impl FromStr for Operand {
+    type Err = String;
+
+    fn from_str(operand: &str) -> Result<Operand, String> {
+        if let Some(register) = REGISTERS.iter().position(|name| *name == operand) {
+            return Ok(Operand::Register(register));
+        }
+
+        operand
+            .parse()
+            .map(Operand::Value)
+            .map_err(|_| format!("Failed to parse operand \"{}\"", operand))
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Register(register) => write!(f, "{}", REGISTERS[*register]),
+            Operand::Value(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Instruction {
+    Noop,
+    Addx(i32),
+    Set(usize, Operand),
+    Add(usize, Operand),
+    Mul(usize, Operand),
+    // Jumps to the instruction after the label, if the operand is not zero for jnz
+    Jump {
+        condition: Option<Operand>,
+        label: String,
+        target: usize,
+    },
+}
+
+impl Instruction {
+    fn opcode(&self) -> Opcode {
+        match self {
+            Instruction::Noop => Opcode::Noop,
+            Instruction::Addx(_) => Opcode::Addx,
+            Instruction::Set(..) => Opcode::Set,
+            Instruction::Add(..) => Opcode::Add,
+            Instruction::Mul(..) => Opcode::Mul,
+            Instruction::Jump {
+                condition: None, ..
+            } => Opcode::Jmp,
+            Instruction::Jump {
+                condition: Some(_), ..
+            } => Opcode::Jnz,
+        }
+    }
+
+    fn mnemonic(&self) -> &'static str {
+        self.opcode().definition().0
+    }
+
+    fn cycles(&self) -> usize {
+        self.opcode().definition().1
+    }
+
+    // Parses a single instruction, looking up jump targets in `labels`
+    fn parse(line: &str, labels: &HashMap<String, usize>) -> Result<Instruction, String> {
+        let components: Vec<&str> = line.split_whitespace().collect();
+        let register = |name: &str| {
+            REGISTERS
+                .iter()
+                .position(|register| *register == name)
+                .ok_or_else(|| format!("Unknown register \"{}\"", name))
+        };
+        let jump = |condition: Option<Operand>, label: &str| {
+            let target = *labels
+                .get(label)
+                .ok_or_else(|| format!("Unknown label \"{}\"", label))?;
+            Ok(Instruction::Jump {
+                condition,
+                label: label.to_string(),
+                target,
+            })
+        };
+
+        match components.as_slice() {
+            ["noop"] => Ok(Instruction::Noop),
+            ["addx", value] => value
+                .parse()
+                .map(Instruction::Addx)
+                .map_err(|_| format!("Failed to parse increment value \"{}\"", value)),
+            ["set", target, operand] => Ok(Instruction::Set(register(target)?, operand.parse()?)),
+            ["add", target, operand] => Ok(Instruction::Add(register(target)?, operand.parse()?)),
+            ["mul", target, operand] => Ok(Instruction::Mul(register(target)?, operand.parse()?)),
+            ["jmp", label] => jump(None, label),
+            ["jnz", operand, label] => jump(Some(operand.parse()?), label),
+            _ => Err(format!("Unknown instruction \"{}\"", line)),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Noop => write!(f, "noop"),
+            Instruction::Addx(value) => write!(f, "addx {}", value),
+            Instruction::Set(register, operand)
+            | Instruction::Add(register, operand)
+            | Instruction::Mul(register, operand) => {
+                write!(
+                    f,
+                    "{} {} {}",
+                    self.mnemonic(),
+                    REGISTERS[*register],
+                    operand
+                )
+            }
+            Instruction::Jump {
+                condition: None,
+                label,
+                ..
+            } => write!(f, "jmp {}", label),
+            Instruction::Jump {
+                condition: Some(operand),
+                label,
+                ..
+            } => write!(f, "jnz {} {}", operand, label),
+        }
+    }
+}
+
+// Parses a program where each line is an instruction or a label written as "name:". A label
+// refers to the instruction following it.
+fn parse_program(lines: &[String]) -> Result<Vec<Instruction>, String> {
+    let mut labels = HashMap::new();
+    let mut instruction_count = 0;
+    for (index, line) in lines.iter().enumerate() {
+        let line = line.trim();
+        if let Some(label) = line.strip_suffix(':') {
+            if labels
+                .insert(label.to_string(), instruction_count)
+                .is_some()
+            {
+                return Err(format!("Line {}: Duplicate label \"{}\"", index + 1, label));
+            }
+        } else if !line.is_empty() {
+            instruction_count += 1;
+        }
+    }
+
+    lines
+        .iter()
+        .enumerate()
+        .map(|(index, line)| (index, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.ends_with(':'))
+        .map(|(index, line)| {
+            Instruction::parse(line, &labels)
+                .map_err(|error| format!("Line {}: {}", index + 1, error))
+        })
+        .collect()
+}
+
+// The state of the machine for a single cycle
+struct Cycle {
+    cycle: usize,
+    instruction: Instruction,
+    x_during: i32,
+    x_after: i32,
 }
 
-#[derive(Default)]
 struct VirtualMachine {
-    instructions: Vec<String>,
-    halfway_in_instruction: bool,
+    instructions: Vec<Instruction>,
+    // How many cycles of the current instruction have already run
+    cycles_in_instruction: usize,
     index: usize,
     cycle: usize,
-    x: i32,
+    registers: [i32; REGISTERS.len()],
+}
+
+impl VirtualMachine {
+    fn new(instructions: Vec<Instruction>) -> VirtualMachine {
+        let mut vm = VirtualMachine {
+            instructions,
+            cycles_in_instruction: 0,
+            index: 0,
+            cycle: 0,
+            registers: [0; REGISTERS.len()],
+        };
+        vm.reset();
+        vm
+    }
+
+    fn reset(&mut self) {
+        self.cycles_in_instruction = 0;
+        self.index = 0;
+        self.cycle = 0;
+        self.registers = [0; REGISTERS.len()];
+        self.registers[X] = 1;
+    }
+
+    fn value(&self, operand: Operand) -> i32 {
+        match operand {
+            Operand::Register(register) => self.registers[register],
+            Operand::Value(value) => value,
+        }
+    }
+
+    // Applies the effect of the current instruction and moves on to the next one. Arithmetic wraps
+    // around on overflow rather than stopping the program.
+    fn execute(&mut self, instruction: &Instruction) {
+        self.index += 1;
+        match instruction {
+            Instruction::Noop => {}
+            Instruction::Addx(value) => self.registers[X] = self.registers[X].wrapping_add(*value),
+            Instruction::Set(register, operand) => self.registers[*register] = self.value(*operand),
+            Instruction::Add(register, operand) => {
+                self.registers[*register] =
+                    self.registers[*register].wrapping_add(self.value(*operand))
+            }
+            Instruction::Mul(register, operand) => {
+                self.registers[*register] =
+                    self.registers[*register].wrapping_mul(self.value(*operand))
+            }
+            Instruction::Jump {
+                condition, target, ..
+            } => {
+                if condition.is_none_or(|operand| self.value(operand) != 0) {
+                    self.index = *target;
+                }
+            }
+        }
+    }
 }
 
 impl Iterator for VirtualMachine {
-    type Item = (i32, i32);
+    type Item = Cycle;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let old_x = self.x;
+        let instruction = self.instructions.get(self.index)?.clone();
+        let x_during = self.registers[X];
         self.cycle += 1;
-        if self.index >= self.instructions.len() {
+        self.cycles_in_instruction += 1;
+
+        if self.cycles_in_instruction == instruction.cycles() {
+            self.execute(&instruction);
+            self.cycles_in_instruction = 0;
+        }
+
+        Some(Cycle {
+            cycle: self.cycle,
+            instruction,
+            x_during,
+            x_after: self.registers[X],
+        })
+    }
+}
+
+// Letters are drawn four pixels wide and six high with a blank column between them
+const GLYPH_WIDTH: usize = 4;
+const GLYPH_HEIGHT: usize = 6;
+const GLYPH_SPACING: usize = 5;
+
+// The letters the puzzles are known to use, drawn as they appear on the CRT
+const GLYPHS: [(char, [&str; GLYPH_HEIGHT]); 18] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Y', ["#...", "#...", ".#.#", "..#.", "..#.", "..#."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+// A pixel is lit if the three pixel wide sprite centred on X covers it
+fn is_lit(x: i32, column: usize) -> bool {
+    (i64::from(x) - column as i64).abs() <= 1
+}
+
+// The lit pixels of the screen stored row by row
+struct Crt {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+}
+
+impl Crt {
+    // Draws a pixel for each cycle, one row after another
+    fn draw(cycles: impl Iterator<Item = Cycle>, width: usize, height: usize) -> Crt {
+        let mut pixels = vec![false; width * height];
+        for (index, cycle) in cycles.take(pixels.len()).enumerate() {
+            pixels[index] = is_lit(cycle.x_during, index % width);
+        }
+
+        Crt {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    fn glyph_count(&self) -> usize {
+        self.width.div_ceil(GLYPH_SPACING)
+    }
+
+    // Draws the glyph at `position`, one row per line
+    fn glyph_rows(&self, position: usize) -> Vec<String> {
+        let left = position * GLYPH_SPACING;
+        self.pixels
+            .chunks(self.width)
+            .map(|row| {
+                row[left..(left + GLYPH_WIDTH).min(self.width)]
+                    .iter()
+                    .map(|lit| if *lit { '#' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Reads the letter at each glyph position, None if it is not in the glyph table. Only a
+    // screen the same height as the glyphs can be read.
+    fn decode(&self) -> Option<Vec<Option<char>>> {
+        if self.height != GLYPH_HEIGHT {
             return None;
         }
 
-        let instruction = &self.instructions[self.index];
-        if instruction.trim() == "noop" {
-            self.index += 1;
-            return Some((old_x, self.x));
+        let letters = (0..self.glyph_count())
+            .map(|position| {
+                let rows = self.glyph_rows(position);
+                GLYPHS
+                    .iter()
+                    .find(|(_, glyph)| rows.iter().zip(glyph.iter()).all(|(a, b)| a == b))
+                    .map(|(letter, _)| *letter)
+            })
+            .collect();
+        Some(letters)
+    }
+}
+
+impl fmt::Display for Crt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in self.pixels.chunks(self.width) {
+            let row: String = row.iter().map(|lit| if *lit { '#' } else { '.' }).collect();
+            writeln!(f, "{}", row)?;
         }
 
-        if self.halfway_in_instruction {
-            self.halfway_in_instruction = false;
-            self.index += 1;
+        Ok(())
+    }
+}
 
-            let increment_value: i32 = instruction
-                .split_once(' ')
-                .unwrap()
-                .1
-                .parse()
-                .expect("Failed to parse increment value");
+// Where the debugger stops when continuing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Breakpoint {
+    // After the cycle with this number has run
+    Cycle(usize),
+    // Before the instruction at this index starts
+    Instruction(usize),
+}
 
-            self.x += increment_value;
-            return Some((old_x, self.x));
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Cycle(cycle) => write!(f, "cycle {}", cycle),
+            Breakpoint::Instruction(index) => write!(f, "instruction {}", index),
         }
+    }
+}
+
+// Stops the debugger when X changes, or only when it changes to the given value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Watchpoint {
+    Change,
+    Value(i32),
+}
 
-        self.halfway_in_instruction = true;
-        Some((old_x, self.x))
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Watchpoint::Change => write!(f, "x changes"),
+            Watchpoint::Value(value) => write!(f, "x becomes {}", value),
+        }
     }
 }
 
+struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    // The pixels of the CRT row currently being drawn
+    row: String,
+    crt_width: usize,
+    crt_height: usize,
+    // Most cycles to run when continuing, as programs with jumps may never finish
+    max_cycles: usize,
+}
+
+impl Debugger {
+    fn new(crt_width: usize, crt_height: usize, max_cycles: usize) -> Debugger {
+        Debugger {
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            row: String::new(),
+            crt_width,
+            crt_height,
+            max_cycles,
+        }
+    }
+
+    // Reads commands until the user quits or the input runs out
+    fn run(&mut self, vm: &mut VirtualMachine) {
+        println!(
+            "Debugging {} instructions, type help for commands",
+            vm.instructions.len()
+        );
+        loop {
+            print!("(debug) ");
+            io::stdout().flush().expect("Failed to flush stdout");
+
+            let mut input = String::new();
+            if io::stdin()
+                .read_line(&mut input)
+                .expect("Failed to read input")
+                == 0
+            {
+                println!();
+                return;
+            }
+
+            let components: Vec<&str> = input.split_whitespace().collect();
+            match components.as_slice() {
+                [] | ["s" | "step"] => self.step(vm, 1),
+                ["s" | "step", count] => match count.parse() {
+                    Ok(count) => self.step(vm, count),
+                    Err(_) => println!("Failed to parse count \"{}\"", count),
+                },
+                ["c" | "continue"] => self.continue_running(vm),
+                ["b" | "break", "cycle", cycle] => match cycle.parse() {
+                    Ok(cycle) => self.add_breakpoint(Breakpoint::Cycle(cycle)),
+                    Err(_) => println!("Failed to parse cycle \"{}\"", cycle),
+                },
+                ["b" | "break", "instruction", index] => match index.parse() {
+                    Ok(index) if index < vm.instructions.len() => {
+                        self.add_breakpoint(Breakpoint::Instruction(index))
+                    }
+                    Ok(index) => println!("There is no instruction {}", index),
+                    Err(_) => println!("Failed to parse instruction index \"{}\"", index),
+                },
+                ["w" | "watch"] => self.add_watchpoint(Watchpoint::Change),
+                ["w" | "watch", value] => match value.parse() {
+                    Ok(value) => self.add_watchpoint(Watchpoint::Value(value)),
+                    Err(_) => println!("Failed to parse value \"{}\"", value),
+                },
+                ["d" | "delete"] => {
+                    self.breakpoints.clear();
+                    self.watchpoints.clear();
+                    println!("Deleted all breakpoints and watchpoints");
+                }
+                ["i" | "info"] => self.print_info(vm),
+                ["l" | "list"] => print_listing(vm),
+                ["q" | "quit"] => return,
+                ["h" | "help"] => print_debugger_help(),
+                _ => println!("Unknown command: {}", input.trim()),
+            }
+        }
+    }
+
+    fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+        println!("Breakpoint at {}", breakpoint);
+    }
+
+    fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        if !self.watchpoints.contains(&watchpoint) {
+            self.watchpoints.push(watchpoint);
+        }
+        println!("Watching for {}", watchpoint);
+    }
+
+    // Runs a single cycle, returning it and drawing its pixel into the current row
+    fn run_cycle(&mut self, vm: &mut VirtualMachine) -> Option<Cycle> {
+        let cycle = vm.next()?;
+        let column = (cycle.cycle - 1) % self.crt_width;
+        if column == 0 {
+            self.row.clear();
+        }
+        self.row.push(if is_lit(cycle.x_during, column) {
+            '#'
+        } else {
+            '.'
+        });
+
+        Some(cycle)
+    }
+
+    fn step(&mut self, vm: &mut VirtualMachine, count: usize) {
+        for _ in 0..count {
+            match self.run_cycle(vm) {
+                Some(cycle) => print_cycle(&cycle),
+                None => {
+                    println!("Program finished after {} cycles", vm.cycle);
+                    return;
+                }
+            }
+        }
+
+        self.print_row(vm);
+    }
+
+    fn continue_running(&mut self, vm: &mut VirtualMachine) {
+        for _ in 0..self.max_cycles {
+            let Some(cycle) = self.run_cycle(vm) else {
+                println!("Program finished after {} cycles", vm.cycle);
+                return;
+            };
+
+            if let Some(reason) = self.stop_reason(vm, &cycle) {
+                print_cycle(&cycle);
+                println!("Stopped at {}", reason);
+                self.print_row(vm);
+                return;
+            }
+        }
+
+        println!("Stopped after running {} cycles", self.max_cycles);
+        self.print_row(vm);
+    }
+
+    // Returns the breakpoint or watchpoint hit by the cycle which has just run, if any
+    fn stop_reason(&self, vm: &VirtualMachine, cycle: &Cycle) -> Option<String> {
+        let breakpoint = self.breakpoints.iter().find(|breakpoint| match breakpoint {
+            Breakpoint::Cycle(number) => cycle.cycle == *number,
+            Breakpoint::Instruction(index) => {
+                vm.cycles_in_instruction == 0
+                    && vm.index == *index
+                    && vm.index < vm.instructions.len()
+            }
+        });
+        if let Some(breakpoint) = breakpoint {
+            return Some(breakpoint.to_string());
+        }
+
+        let watchpoint = self.watchpoints.iter().find(|watchpoint| {
+            cycle.x_after != cycle.x_during
+                && match watchpoint {
+                    Watchpoint::Change => true,
+                    Watchpoint::Value(value) => cycle.x_after == *value,
+                }
+        });
+        watchpoint.map(|watchpoint| watchpoint.to_string())
+    }
+
+    fn print_info(&self, vm: &VirtualMachine) {
+        println!("Cycles run: {}", vm.cycle);
+        match vm.instructions.get(vm.index) {
+            Some(instruction) => println!(
+                "Instruction {}: {} ({} of {} cycles run)",
+                vm.index,
+                instruction,
+                vm.cycles_in_instruction,
+                instruction.cycles()
+            ),
+            None => println!("Program finished"),
+        }
+
+        let registers: Vec<String> = REGISTERS
+            .iter()
+            .zip(vm.registers.iter())
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        println!("Registers: {}", registers.join(" "));
+
+        for breakpoint in self.breakpoints.iter() {
+            println!("Breakpoint at {}", breakpoint);
+        }
+        for watchpoint in self.watchpoints.iter() {
+            println!("Watching for {}", watchpoint);
+        }
+        self.print_row(vm);
+    }
+
+    // Draws the CRT row so far with the sprite, as it will be for the next cycle, underneath
+    fn print_row(&self, vm: &VirtualMachine) {
+        if vm.cycle == 0 {
+            return;
+        }
+
+        let x = vm.registers[X];
+        let sprite: String = (0..self.crt_width)
+            .map(|column| if is_lit(x, column) { '#' } else { ' ' })
+            .collect();
+        println!(
+            "Row {:>2}: {}",
+            (vm.cycle - 1) / self.crt_width % self.crt_height + 1,
+            self.row
+        );
+        println!("Sprite: {}", sprite.trim_end());
+    }
+}
+
+fn print_cycle(cycle: &Cycle) {
+    println!(
+        "{:>5}  {:<11}  {:>8}  {:>7}",
+        cycle.cycle,
+        cycle.instruction.to_string(),
+        cycle.x_during,
+        cycle.x_after
+    );
+}
+
+// Lists the program with its instruction indexes, marking the next instruction to run
+fn print_listing(vm: &VirtualMachine) {
+    for (index, instruction) in vm.instructions.iter().enumerate() {
+        let marker = if index == vm.index { '>' } else { ' ' };
+        println!("{} {:>4}  {}", marker, index, instruction);
+    }
+}
+
+fn print_debugger_help() {
+    println!("step [count]              Run one or more cycles");
+    println!("continue                  Run until a breakpoint or watchpoint is hit");
+    println!("break cycle <number>      Stop after the given cycle");
+    println!("break instruction <index> Stop before the instruction at the given index starts");
+    println!("watch [value]             Stop when x changes, or changes to the given value");
+    println!("delete                    Remove all breakpoints and watchpoints");
+    println!("info                      Show the registers, breakpoints and current CRT row");
+    println!("list                      Show the program");
+    println!("quit                      Stop debugging and run the program normally");
+}
+
 fn main() {
     let args = Args::parse();
 
+    if args.crt_width == 0 || args.crt_height == 0 {
+        panic!("The CRT must be at least one pixel wide and high");
+    }
+    let sample_cycles = parse_sample_cycles(&args);
+
     let file = File::open(&args.data_file).expect("Failed to open file");
     let reader = BufReader::new(file);
 
-    let mut vm = VirtualMachine {
-        instructions: reader
-            .lines()
-            .map(|line| line.expect("Failed to parse line"))
-            .collect(),
-        x: 1,
-        ..Default::default()
-    };
+    let lines: Vec<String> = reader
+        .lines()
+        .map(|line| line.expect("Failed to parse line"))
+        .collect();
+    let instructions = parse_program(&lines).unwrap_or_else(|error| panic!("{}", error));
+    let mut vm = VirtualMachine::new(instructions);
 
-    // The text is odd because we want the X value DURING the cycle which is actually the value after the previous cycle.
-    // Adding 18 brings us to the 19th cycle so the call to next() will give the 20th cycle.
-    vm.nth(18);
-    let mut total = 0;
-    for i in 0..6 {
-        let result = vm.next();
-        // Adding 38 will increase the iterator by 39 when combined with the above next()
-        vm.nth(38);
-        total += result.unwrap().0 * ((i * 40) + 20);
+    if args.trace {
+        println!("Cycle  Instruction  X during  X after");
+        for cycle in vm.by_ref().take(args.max_cycles) {
+            print_cycle(&cycle);
+        }
+        vm.reset();
     }
 
-    // 19, 59
-    // 19 + 39 + 1 = 59
+    if args.debug {
+        Debugger::new(args.crt_width, args.crt_height, args.max_cycles).run(&mut vm);
+        vm.reset();
+    }
 
+    // The signal strength is sampled using the value of X during the cycle, not after it
+    let last_sample = sample_cycles.last().copied().unwrap_or(0);
+    let samples: Vec<Cycle> = vm
+        .by_ref()
+        .take_while(|cycle| cycle.cycle <= last_sample)
+        .filter(|cycle| sample_cycles.binary_search(&cycle.cycle).is_ok())
+        .collect();
+    if let Some(missed) = sample_cycles.get(samples.len()) {
+        eprintln!(
+            "Warning: the program finished before cycle {}, which was not sampled",
+            missed
+        );
+    }
+
+    let total: i64 = samples
+        .iter()
+        .map(|cycle| cycle.cycle as i64 * i64::from(cycle.x_during))
+        .sum();
     println!("Total power is {}", total);
 
     // PART 2
-    // reset the VM
-    vm.x = 1;
-    vm.cycle = 0;
-    vm.index = 0;
-    vm.halfway_in_instruction = false;
-
-    for (index, x) in vm.enumerate() {
-        let row_index = index % 40;
-        print!(
-            "{}",
-            if ((x.0 - row_index as i32) as i32).abs() <= 1 {
-                "#"
-            } else {
-                "."
-            }
+    vm.reset();
+
+    let crt = Crt::draw(vm, args.crt_width, args.crt_height);
+    print!("{}", crt);
+
+    let Some(letters) = crt.decode() else {
+        println!(
+            "Letters can only be read from a CRT {} rows high",
+            GLYPH_HEIGHT
         );
-        if row_index == 39 {
-            print!("\n");
+        return;
+    };
+    let text: String = letters.iter().map(|letter| letter.unwrap_or('?')).collect();
+    for (position, _) in letters
+        .iter()
+        .enumerate()
+        .filter(|(_, letter)| letter.is_none())
+    {
+        eprintln!("Unrecognised glyph at position {}:", position + 1);
+        for row in crt.glyph_rows(position) {
+            eprintln!("  {}", row);
         }
     }
+
+    println!("CRT reads {}", text);
+}
+
+// Returns the cycles to sample in order, either from the list given or every interval from the
+// start until the end
+fn parse_sample_cycles(args: &Args) -> Vec<usize> {
+    let mut cycles: Vec<usize> = match &args.sample_cycles {
+        Some(list) => list
+            .split(',')
+            .map(|cycle| {
+                cycle
+                    .trim()
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Failed to parse sample cycle \"{}\"", cycle))
+            })
+            .collect(),
+        None => {
+            if args.sample_interval == 0 {
+                panic!("Sample interval must be at least 1");
+            }
+            (args.sample_start..=args.sample_end)
+                .step_by(args.sample_interval)
+                .collect()
+        }
+    };
+
+    if cycles.contains(&0) {
+        panic!("Cycles are numbered from 1");
+    }
+    cycles.sort_unstable();
+    cycles.dedup();
+    cycles
 }
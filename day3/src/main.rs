@@ -1,8 +1,8 @@
 use clap::Parser;
 use std::{
-    collections::HashSet,
     fs::File,
     io::{BufRead, BufReader},
+    num::NonZeroUsize,
 };
 
 #[derive(Parser, Debug)]
@@ -10,6 +10,68 @@ use std::{
 struct Args {
     #[arg(long)]
     data_file: String,
+    /// Number of rucksacks in each group sharing a badge
+    #[arg(long, default_value = "3")]
+    group_size: NonZeroUsize,
+}
+
+// Each item is stored as a single bit in a u64 where bit (priority - 1) is set, so a-z occupy
+// bits 0-25 and A-Z occupy bits 26-51.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Items(u64);
+
+impl Items {
+    fn new(items: &str) -> Items {
+        Items(
+            items
+                .bytes()
+                .fold(0, |mask, item| mask | 1 << (item_priority(item) - 1)),
+        )
+    }
+
+    fn common_with(&self, other: &Items) -> Items {
+        Items(self.0 & other.0)
+    }
+
+    fn item_count(&self) -> u32 {
+        self.0.count_ones()
+    }
+
+    fn priorities(&self) -> impl Iterator<Item = u32> {
+        let items = self.0;
+        (1..=52).filter(move |priority| items & 1 << (priority - 1) != 0)
+    }
+
+    fn priority_sum(&self) -> u32 {
+        self.priorities().sum()
+    }
+}
+
+// The items in each of the two compartments of a rucksack
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Rucksack {
+    left: Items,
+    right: Items,
+}
+
+impl Rucksack {
+    fn new(items: &str) -> Rucksack {
+        // Each backpack is an even number of items split among two pockets
+        let (left, right) = items.split_at(items.len() / 2);
+
+        Rucksack {
+            left: Items::new(left),
+            right: Items::new(right),
+        }
+    }
+
+    fn items(&self) -> Items {
+        Items(self.left.0 | self.right.0)
+    }
+
+    fn compartments(&self) -> (Items, Items) {
+        (self.left, self.right)
+    }
 }
 
 fn main() {
@@ -21,8 +83,11 @@ fn main() {
     let priority_sum: u32 = reader
         .lines()
         .map(|line| line.expect("Failed to create line"))
-        .map(find_repeated_item)
-        .map(item_priority)
+        .map(|line| Rucksack::new(&line))
+        .map(|rucksack| {
+            let (left, right) = rucksack.compartments();
+            left.common_with(&right).priority_sum()
+        })
         .sum();
 
     println!("Part 1 total priority: {}", priority_sum);
@@ -32,46 +97,79 @@ fn main() {
 
     let mut current_elves = Vec::new();
     let mut part2_priority_total = 0;
+    let mut group_number = 0;
     for line in reader.lines() {
-        current_elves.push(line.expect("Failed to get line"));
-        if current_elves.len() == 3 {
-            let repeated_item = *current_elves
-                .iter()
-                .map(|e| e.chars().collect::<HashSet<char>>())
-                .reduce(|a, e| a.intersection(&e).map(|c| *c).collect::<HashSet<char>>())
-                .expect("Failed to find repeated item")
-                .iter()
-                .last()
-                .expect("No repeated item found");
-
-            part2_priority_total += item_priority(repeated_item);
+        current_elves.push(Rucksack::new(&line.expect("Failed to get line")));
+        if current_elves.len() == args.group_size.get() {
+            group_number += 1;
+            if let Some(badge) = find_badge(group_number, &current_elves) {
+                let priority = badge.priority_sum();
+                println!(
+                    "Group {}: badge {} (priority {})",
+                    group_number,
+                    priority_item(priority),
+                    priority
+                );
+                part2_priority_total += priority;
+            }
 
             current_elves = Vec::new();
         }
     }
 
+    if !current_elves.is_empty() {
+        eprintln!(
+            "Trailing incomplete group of {} elves (expected {}) was ignored",
+            current_elves.len(),
+            args.group_size.get()
+        );
+    }
+
     println!("Part 2 total priority: {}", part2_priority_total);
 }
 
-fn find_repeated_item(backpack: String) -> char {
-    // Each backpack is an even number of items split among two pockets
-    let (left, right) = backpack.split_at(backpack.len() / 2);
-    let left: HashSet<char> = left.chars().collect();
-    let right: HashSet<char> = right.chars().collect();
+fn find_badge(group_number: usize, elves: &[Rucksack]) -> Option<Items> {
+    let common_items = elves
+        .iter()
+        .map(Rucksack::items)
+        .reduce(|a, e| a.common_with(&e))
+        .expect("Group has no elves");
 
-    *left
-        .intersection(&right)
-        .last()
-        .expect("Failed to find intersection")
+    match common_items.item_count() {
+        0 => {
+            eprintln!("Group {}: no common item found", group_number);
+            None
+        }
+        1 => Some(common_items),
+        _ => {
+            eprintln!(
+                "Group {}: multiple common items found: {}",
+                group_number,
+                common_items
+                    .priorities()
+                    .map(priority_item)
+                    .collect::<String>()
+            );
+            None
+        }
+    }
 }
 
-fn item_priority(item: char) -> u32 {
+fn item_priority(item: u8) -> u32 {
     // a-z are 1-26
     // A-Z are 27-52
 
-    if item.is_ascii_uppercase() {
-        return u32::from(item) - u32::from('A') + 27;
+    match item {
+        b'a'..=b'z' => u32::from(item - b'a') + 1,
+        b'A'..=b'Z' => u32::from(item - b'A') + 27,
+        _ => panic!("Unexpected item: {}", char::from(item)),
+    }
+}
+
+fn priority_item(priority: u32) -> char {
+    if priority > 26 {
+        return char::from(b'A' + (priority - 27) as u8);
     }
 
-    u32::from(item) - u32::from('a') + 1
+    char::from(b'a' + (priority - 1) as u8)
 }
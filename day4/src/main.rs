@@ -1,5 +1,6 @@
 use clap::Parser;
 use std::{
+    fmt,
     fs::File,
     io::{BufRead, BufReader},
 };
@@ -9,6 +10,161 @@ use std::{
 struct Args {
     #[arg(long)]
     data_file: String,
+    /// Print the overlapping sections of every pair of members
+    #[arg(long)]
+    report_pairs: bool,
+    /// Print the overlap between every pair of members in each team
+    #[arg(long)]
+    report_teams: bool,
+}
+
+// An inclusive range of section ids
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct SectionRange {
+    start: u32,
+    end: u32,
+}
+
+impl SectionRange {
+    fn new(start: u32, end: u32) -> SectionRange {
+        if start > end {
+            panic!("Invalid section range {}-{}", start, end);
+        }
+
+        SectionRange { start, end }
+    }
+
+    fn len(&self) -> u64 {
+        u64::from(self.end - self.start) + 1
+    }
+
+    fn contains(&self, other: &SectionRange) -> bool {
+        self.start <= other.start && self.end >= other.end
+    }
+
+    fn overlaps(&self, other: &SectionRange) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+
+    fn intersection(&self, other: &SectionRange) -> Option<SectionRange> {
+        if !self.overlaps(other) {
+            return None;
+        }
+
+        Some(SectionRange::new(
+            self.start.max(other.start),
+            self.end.min(other.end),
+        ))
+    }
+}
+
+impl fmt::Display for SectionRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+// A set of sections stored as sorted, disjoint and non-adjacent ranges
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct SectionSet {
+    ranges: Vec<SectionRange>,
+}
+
+impl SectionSet {
+    fn from_ranges(ranges: impl IntoIterator<Item = SectionRange>) -> SectionSet {
+        let mut ranges: Vec<SectionRange> = ranges.into_iter().collect();
+        ranges.sort_by_key(|r| r.start);
+
+        let mut merged: Vec<SectionRange> = Vec::new();
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end.saturating_add(1) => {
+                    last.end = last.end.max(range.end);
+                }
+                _ => merged.push(range),
+            }
+        }
+
+        SectionSet { ranges: merged }
+    }
+
+    fn len(&self) -> u64 {
+        self.ranges.iter().map(SectionRange::len).sum()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    fn contains(&self, other: &SectionSet) -> bool {
+        other
+            .ranges
+            .iter()
+            .all(|o| self.ranges.iter().any(|r| r.contains(o)))
+    }
+
+    fn union(&self, other: &SectionSet) -> SectionSet {
+        SectionSet::from_ranges(self.ranges.iter().chain(other.ranges.iter()).copied())
+    }
+
+    fn intersection(&self, other: &SectionSet) -> SectionSet {
+        SectionSet::from_ranges(
+            self.ranges
+                .iter()
+                .flat_map(|r| other.ranges.iter().filter_map(|o| r.intersection(o))),
+        )
+    }
+
+    fn difference(&self, other: &SectionSet) -> SectionSet {
+        let mut remaining = self.ranges.clone();
+        for o in other.ranges.iter() {
+            remaining = remaining
+                .into_iter()
+                .flat_map(|r| {
+                    if !r.overlaps(o) {
+                        return vec![r];
+                    }
+
+                    let mut pieces = Vec::new();
+                    if r.start < o.start {
+                        pieces.push(SectionRange::new(r.start, o.start - 1));
+                    }
+                    if r.end > o.end {
+                        pieces.push(SectionRange::new(o.end + 1, r.end));
+                    }
+                    pieces
+                })
+                .collect();
+        }
+
+        SectionSet::from_ranges(remaining)
+    }
+
+    fn bounds(&self) -> Option<SectionRange> {
+        Some(SectionRange::new(
+            self.ranges.first()?.start,
+            self.ranges.last()?.end,
+        ))
+    }
+}
+
+impl From<SectionRange> for SectionSet {
+    fn from(range: SectionRange) -> SectionSet {
+        SectionSet {
+            ranges: vec![range],
+        }
+    }
+}
+
+impl fmt::Display for SectionSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "none");
+        }
+
+        let ranges: Vec<String> = self.ranges.iter().map(|r| r.to_string()).collect();
+        write!(f, "{}", ranges.join(","))
+    }
 }
 
 fn main() {
@@ -17,43 +173,119 @@ fn main() {
     let file = File::open(&args.data_file).expect("Failed to open file");
     let reader = BufReader::new(file);
 
-    let ranges: Vec<((u32, u32), (u32, u32))> = reader
+    let teams: Vec<Vec<SectionRange>> = reader
         .lines()
         .map(|l| l.expect("Failed to parse line"))
         .map(parse_ranges)
         .collect();
 
-    let fully_overlapped_count = ranges.iter().filter(only_one_assignment_needed).count();
+    let fully_overlapped_count = teams
+        .iter()
+        .filter(|team| !redundant_members(team).is_empty())
+        .count();
     println!("Fully overlapped count: {}", fully_overlapped_count);
 
-    let partially_overlapped_count = ranges.iter().filter(partially_overlapped).count();
+    let partially_overlapped_count = teams
+        .iter()
+        .filter(|team| {
+            team.iter()
+                .enumerate()
+                .any(|(i, a)| team.iter().skip(i + 1).any(|b| a.overlaps(b)))
+        })
+        .count();
     println!("Partially overlapped count: {}", partially_overlapped_count);
+
+    if args.report_pairs {
+        for team in teams.iter() {
+            for (i, left) in team.iter().enumerate() {
+                for right in team.iter().skip(i + 1) {
+                    let overlap = SectionSet::from(*left).intersection(&SectionSet::from(*right));
+                    println!(
+                        "{},{}: {} overlapping sections: {}",
+                        left,
+                        right,
+                        overlap.len(),
+                        overlap
+                    );
+                }
+            }
+        }
+    }
+
+    if args.report_teams {
+        for team in teams.iter() {
+            print_team_report(team);
+        }
+    }
+
+    let covered = SectionSet::from_ranges(teams.iter().flatten().copied());
+    println!("Total sections covered: {}", covered.len());
+
+    let unassigned = match covered.bounds() {
+        Some(bounds) => SectionSet::from(bounds).difference(&covered),
+        None => SectionSet::default(),
+    };
+    println!("Unassigned sections: {}: {}", unassigned.len(), unassigned);
 }
 
-fn parse_ranges(input: String) -> ((u32, u32), (u32, u32)) {
-    let (left, right) = input.split_once(',').expect("Failed to split at ','");
-    let (left_start, left_end) = left.split_once('-').expect("Failed to split on '-'");
-    let (right_start, right_end) = right.split_once('-').expect("Failed to split on '-'");
+// Returns the indices of team members whose assignment is entirely covered by another member.
+// When two members have identical assignments only the later one is considered redundant.
+fn redundant_members(team: &[SectionRange]) -> Vec<usize> {
+    (0..team.len())
+        .filter(|&i| {
+            let member = SectionSet::from(team[i]);
+            team.iter().enumerate().any(|(j, other)| {
+                i != j && SectionSet::from(*other).contains(&member) && (other != &team[i] || j < i)
+            })
+        })
+        .collect()
+}
 
-    let left_start: u32 = left_start.parse().expect("Failed to parse number");
-    let left_end: u32 = left_end.parse().expect("Failed to parse number");
-    let right_start: u32 = right_start.parse().expect("Failed to parse number");
-    let right_end: u32 = right_end.parse().expect("Failed to parse number");
+fn print_team_report(team: &[SectionRange]) {
+    let names: Vec<String> = team.iter().map(|r| r.to_string()).collect();
+    println!("Team {}", names.join(","));
 
-    ((left_start, left_end), (right_start, right_end))
+    let redundant: Vec<&str> = redundant_members(team)
+        .into_iter()
+        .map(|i| names[i].as_str())
+        .collect();
+    if redundant.is_empty() {
+        println!("  Redundant members: none");
+    } else {
+        println!("  Redundant members: {}", redundant.join(","));
+    }
+
+    let covered = team.iter().fold(SectionSet::default(), |covered, member| {
+        covered.union(&SectionSet::from(*member))
+    });
+    println!("  Sections covered: {}: {}", covered.len(), covered);
+
+    // Overlap matrix, the diagonal is the size of each member's own assignment
+    let width = names.iter().map(|n| n.len()).max().unwrap_or(0);
+    print!("  {:width$}", "", width = width);
+    for name in names.iter() {
+        print!(" {:>width$}", name, width = width);
+    }
+    println!();
+    for (i, a) in team.iter().enumerate() {
+        print!("  {:>width$}", names[i], width = width);
+        for b in team.iter() {
+            let overlap = SectionSet::from(*a).intersection(&SectionSet::from(*b));
+            print!(" {:>width$}", overlap.len(), width = width);
+        }
+        println!();
+    }
 }
 
-fn only_one_assignment_needed(
-    ((left_start, left_end), (right_start, right_end)): &&((u32, u32), (u32, u32)),
-) -> bool {
-    // Check if there is a complete overlap
-    return (left_start <= right_start && left_end >= right_end)
-        || (right_start <= left_start && right_end >= left_end);
+fn parse_ranges(input: String) -> Vec<SectionRange> {
+    input.split(',').map(parse_range).collect()
 }
 
-fn partially_overlapped(
-    ((left_start, left_end), (right_start, right_end)): &&((u32, u32), (u32, u32)),
-) -> bool {
-    return (left_start <= right_start && left_end >= right_start)
-        || (right_start <= left_start && right_end >= left_start);
+fn parse_range(input: &str) -> SectionRange {
+    let (start, end) = input.split_once('-').expect("Failed to split on '-'");
+
+    let start: u32 = start.parse().expect("Failed to parse number");
+    let end: u32 = end.parse().expect("Failed to parse number");
+
+    SectionRange::new(start, end)
 }
@@ -1,7 +1,10 @@
 use clap::Parser;
 use std::{
+    fmt, fs,
     fs::File,
-    io::{BufRead, BufReader},
+    io::{self, BufRead, BufReader, Write},
+    process,
+    str::FromStr,
 };
 
 #[derive(Parser, Debug)]
@@ -9,13 +12,181 @@ use std::{
 struct Args {
     #[arg(long)]
     data_file: String,
+    /// Crane model to use, either 9000 or 9001
+    #[arg(long, default_value_t = 9000)]
+    crane: u32,
+    /// Use a custom crane which can lift at most this many crates at once, instead of a crane model
+    #[arg(long, conflicts_with = "crane")]
+    max_lift: Option<usize>,
+    /// Step through the moves, drawing the stacks after each one
     #[arg(long)]
-    part: u32,
+    replay: bool,
+    /// Write the final stacks to this file so they can be used as the starting state of another run
+    #[arg(long)]
+    save_state: Option<String>,
+}
+
+// A move is listed as "move N from A to B"
+#[derive(Clone, Copy, Debug)]
+struct Move {
+    count: usize,
+    from: usize,
+    to: usize,
+}
+
+// The crate stacks, indexed from the bottom crate up. These parse from and display as the
+// drawing at the top of the puzzle input, including the numbered footer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Stacks {
+    stacks: Vec<Vec<char>>,
+}
+
+impl Stacks {
+    fn top_crates(&self) -> String {
+        self.stacks
+            .iter()
+            .filter_map(|stack| stack.last())
+            .collect()
+    }
+}
+
+impl FromStr for Stacks {
+    type Err = String;
+
+    fn from_str(drawing: &str) -> Result<Stacks, String> {
+        let lines: Vec<&str> = drawing.lines().collect();
+        let (footer, rows) = lines
+            .split_last()
+            .ok_or_else(|| "drawing is empty".to_string())?;
+        let column_count = footer.split_whitespace().count();
+        let mut stacks: Vec<Vec<char>> = vec![Vec::new(); column_count];
+
+        for (level, row) in rows.iter().rev().enumerate() {
+            // Each item takes 3 chars with the middle char being the ID.
+            // Then there is a space if it is not the last item
+            let row: Vec<char> = row.chars().collect();
+            if row.len() > column_count * 4 {
+                return Err(format!(
+                    "row \"{}\" is wider than the footer",
+                    row.iter().collect::<String>()
+                ));
+            }
+
+            for (c, stack) in stacks.iter_mut().enumerate() {
+                match row.get((c * 4) + 1) {
+                    Some(item) if !item.is_whitespace() => {
+                        if stack.len() != level {
+                            return Err(format!("crate {} in stack {} is floating", item, c + 1));
+                        }
+                        stack.push(*item);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(Stacks { stacks })
+    }
+}
+
+impl fmt::Display for Stacks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let height = self
+            .stacks
+            .iter()
+            .map(|stack| stack.len())
+            .max()
+            .unwrap_or(0);
+        for level in (0..height).rev() {
+            let row: Vec<String> = self
+                .stacks
+                .iter()
+                .map(|stack| match stack.get(level) {
+                    Some(item) => format!("[{}]", item),
+                    None => "   ".to_string(),
+                })
+                .collect();
+            writeln!(f, "{}", row.join(" "))?;
+        }
+
+        let footer: Vec<String> = (1..=self.stacks.len())
+            .map(|index| format!(" {} ", index))
+            .collect();
+        write!(f, "{}", footer.join(" "))
+    }
+}
+
+trait Crane {
+    fn name(&self) -> String;
+    fn apply(&self, stacks: &mut Stacks, crane_move: &Move);
+}
+
+// Moves crates one at a time, so a group of crates ends up in reverse order
+struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn name(&self) -> String {
+        "CrateMover 9000".to_string()
+    }
+
+    fn apply(&self, stacks: &mut Stacks, crane_move: &Move) {
+        LimitedCrane { max_lift: 1 }.apply(stacks, crane_move);
+    }
+}
+
+// Moves all of the crates at once, keeping their order
+struct CrateMover9001;
+
+impl Crane for CrateMover9001 {
+    fn name(&self) -> String {
+        "CrateMover 9001".to_string()
+    }
+
+    fn apply(&self, stacks: &mut Stacks, crane_move: &Move) {
+        LimitedCrane {
+            max_lift: crane_move.count,
+        }
+        .apply(stacks, crane_move);
+    }
+}
+
+// Moves up to max_lift crates at a time, keeping the order of each lift
+struct LimitedCrane {
+    max_lift: usize,
+}
+
+impl Crane for LimitedCrane {
+    fn name(&self) -> String {
+        format!("Crane lifting up to {} crates", self.max_lift)
+    }
+
+    fn apply(&self, stacks: &mut Stacks, crane_move: &Move) {
+        let mut remaining = crane_move.count;
+        while remaining > 0 {
+            let lift = remaining.min(self.max_lift);
+            remaining -= lift;
+
+            let from_stack = stacks.stacks.get_mut(crane_move.from - 1).unwrap();
+            let mut items = from_stack.split_off(from_stack.len() - lift);
+            stacks
+                .stacks
+                .get_mut(crane_move.to - 1)
+                .unwrap()
+                .append(&mut items);
+        }
+    }
 }
 
 fn main() {
     let args = Args::parse();
-    let part_number = args.part;
+
+    let crane: Box<dyn Crane> = match (args.max_lift, args.crane) {
+        (Some(0), _) => panic!("A crane must be able to lift at least one crate"),
+        (Some(max_lift), _) => Box::new(LimitedCrane { max_lift }),
+        (None, 9000) => Box::new(CrateMover9000),
+        (None, 9001) => Box::new(CrateMover9001),
+        (None, model) => panic!("Unknown crane model: {}", model),
+    };
 
     let file = File::open(&args.data_file).expect("Failed to open file");
     let reader = BufReader::new(file);
@@ -24,67 +195,160 @@ fn main() {
         .map(|line| line.expect("Failed to read line"))
         .collect();
 
-    let header: Vec<&String> = lines.iter().take_while(|line| !line.is_empty()).collect();
-    let column_count = header.last().unwrap().split_whitespace().count();
-    let mut stacks: Vec<Vec<String>> = vec![Vec::new(); column_count];
-
-    for header_line in header.iter().rev().skip(1) {
-        // Each item takes 3 chars with the middle char being the ID.
-        // Then there is a space if it is not the last item
-        for c in 0..column_count {
-            let item = header_line
-                .chars()
-                .nth((c * 4) + 1)
-                .expect("Failed to get item");
-            if !item.is_whitespace() {
-                stacks.get_mut(c).unwrap().push(item.to_string());
+    let header: Vec<&str> = lines
+        .iter()
+        .take_while(|line| !line.is_empty())
+        .map(|line| line.as_str())
+        .collect();
+    let mut stacks: Stacks = header
+        .join("\n")
+        .parse()
+        .unwrap_or_else(|error| panic!("Failed to parse stacks: {}", error));
+
+    // Now that we have the initial stacks, lets execute the moves
+    let header_length = header.len() + 1;
+    let moves: Vec<(usize, &String, Move)> = lines
+        .iter()
+        .enumerate()
+        .skip(header_length)
+        .map(|(index, line)| match parse_move(line) {
+            Ok(crane_move) => (index + 1, line, crane_move),
+            Err(error) => exit_with_error(index + 1, line, &error),
+        })
+        .collect();
+
+    // Stacks before each move made while stepping, so that it can be undone
+    let mut history: Vec<Stacks> = Vec::new();
+    let mut next_move = 0;
+    let mut stepping = args.replay;
+    if args.replay {
+        println!("{}", stacks);
+    }
+
+    while next_move < moves.len() {
+        if stepping {
+            match prompt_replay_step() {
+                ReplayStep::Forward => {}
+                ReplayStep::Back => {
+                    match history.pop() {
+                        Some(previous) => {
+                            stacks = previous;
+                            next_move -= 1;
+                            println!("Undid {}", moves[next_move].1);
+                            println!("{}", stacks);
+                        }
+                        None => println!("Already at the starting position"),
+                    }
+                    continue;
+                }
+                ReplayStep::Continue => stepping = false,
+                ReplayStep::Quit => return,
             }
         }
+
+        let (line_number, line, crane_move) = &moves[next_move];
+        if let Err(error) = validate_move(&stacks, crane_move) {
+            exit_with_error(*line_number, line, &error);
+        }
+
+        if stepping {
+            history.push(stacks.clone());
+        }
+        crane.apply(&mut stacks, crane_move);
+        next_move += 1;
+
+        if args.replay {
+            println!("Line {}: {}", line_number, line);
+            println!("{}", stacks);
+        }
     }
 
-    // Now that we have the initial stacks, lets execute the moves
-    // A move is listed as "move N from A to B"
-    for line in lines.iter().skip_while(|line| !line.is_empty()).skip(1) {
-        let components: Vec<&str> = line.split_whitespace().collect();
-        let mut count: usize = components
-            .get(1)
-            .unwrap()
-            .parse()
-            .expect("Failed to parse count");
-        let from_index: usize = components
-            .get(3)
-            .unwrap()
-            .parse()
-            .expect("Failed to parse from");
-        let to_index: usize = components
-            .get(5)
-            .unwrap()
-            .parse()
-            .expect("Failed to parse to");
-
-        if part_number == 1 {
-            while count > 0 {
-                count -= 1;
-                let item = stacks.get_mut(from_index - 1).unwrap().pop().unwrap();
-                stacks.get_mut(to_index - 1).unwrap().push(item);
-            }
-        } else {
-            let from_stack = stacks.get_mut(from_index - 1).unwrap();
-            let mut items: Vec<String> = {
-                let (_, items) = from_stack.split_at(from_stack.len() - count);
-                items.into_iter().map(|item| item.clone()).collect()
-            };
-            from_stack.truncate(from_stack.len() - count);
-            let to_stack = stacks.get_mut(to_index - 1).unwrap();
-            to_stack.append(&mut items);
+    println!("Top crates using {}: {}", crane.name(), stacks.top_crates());
+
+    if let Some(save_state) = args.save_state {
+        fs::write(&save_state, format!("{}\n\n", stacks)).expect("Failed to write state");
+    }
+}
+
+enum ReplayStep {
+    Forward,
+    Back,
+    Continue,
+    Quit,
+}
+
+fn prompt_replay_step() -> ReplayStep {
+    loop {
+        print!("[n]ext, [b]ack, [c]ontinue, [q]uit> ");
+        io::stdout().flush().expect("Failed to flush stdout");
+
+        let mut input = String::new();
+        if io::stdin()
+            .read_line(&mut input)
+            .expect("Failed to read input")
+            == 0
+        {
+            // Nothing more to read so run the remaining moves
+            println!();
+            return ReplayStep::Continue;
+        }
+
+        match input.trim() {
+            "" | "n" => return ReplayStep::Forward,
+            "b" => return ReplayStep::Back,
+            "c" => return ReplayStep::Continue,
+            "q" => return ReplayStep::Quit,
+            other => println!("Unknown command: {}", other),
         }
     }
+}
 
-    for c in 0..column_count {
-        print!("{}: ", c);
-        for item in stacks.get(c).unwrap().iter() {
-            print!("{} ", item);
+fn exit_with_error(line_number: usize, line: &str, error: &str) -> ! {
+    eprintln!("Invalid move on line {} ({}): {}", line_number, line, error);
+    process::exit(1);
+}
+
+fn validate_move(stacks: &Stacks, crane_move: &Move) -> Result<(), String> {
+    for index in [crane_move.from, crane_move.to] {
+        if index == 0 || index > stacks.stacks.len() {
+            return Err(format!(
+                "stack {} does not exist, there are {} stacks",
+                index,
+                stacks.stacks.len()
+            ));
         }
-        print!("\n");
     }
+
+    let available = stacks.stacks[crane_move.from - 1].len();
+    if crane_move.count > available {
+        return Err(format!(
+            "cannot move {} crates from stack {} which only has {}",
+            crane_move.count, crane_move.from, available
+        ));
+    }
+
+    Ok(())
+}
+
+fn parse_move(line: &str) -> Result<Move, String> {
+    let components: Vec<&str> = line.split_whitespace().collect();
+    if components.len() != 6
+        || components[0] != "move"
+        || components[2] != "from"
+        || components[4] != "to"
+    {
+        return Err("expected \"move N from A to B\"".to_string());
+    }
+
+    let count: usize = components[1]
+        .parse()
+        .map_err(|_| format!("failed to parse count \"{}\"", components[1]))?;
+    let from: usize = components[3]
+        .parse()
+        .map_err(|_| format!("failed to parse from \"{}\"", components[3]))?;
+    let to: usize = components[5]
+        .parse()
+        .map_err(|_| format!("failed to parse to \"{}\"", components[5]))?;
+
+    Ok(Move { count, from, to })
 }
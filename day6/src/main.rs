@@ -1,47 +1,169 @@
 use clap::Parser;
-use std::collections::{HashSet, VecDeque};
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, Read, Write},
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    /// File to scan, reads from stdin if not given or if "-"
     #[arg(long)]
-    data_file: String,
+    data_file: Option<String>,
+    /// Marker window sizes to search for, defaults to the packet (4) and message (14) markers
+    #[arg(long = "window")]
+    windows: Vec<usize>,
+    /// Report every marker position rather than only the first
+    #[arg(long)]
+    all: bool,
+    /// Number of bytes to read from the data at a time
+    #[arg(long, default_value_t = 64 * 1024)]
+    buffer_size: usize,
+}
+
+// Tracks how many times each byte appears in the last `window` bytes so that each new byte can be
+// checked in constant time.
+struct MarkerDetector {
+    window: usize,
+    recent: VecDeque<u8>,
+    counts: [usize; 256],
+    distinct: usize,
+    found: bool,
+}
+
+impl MarkerDetector {
+    fn new(window: usize) -> MarkerDetector {
+        MarkerDetector {
+            window,
+            recent: VecDeque::with_capacity(window + 1),
+            counts: [0; 256],
+            distinct: 0,
+            found: false,
+        }
+    }
+
+    // Adds the next byte, returning true if the last `window` bytes are all different
+    fn push(&mut self, byte: u8) -> bool {
+        self.recent.push_back(byte);
+        self.counts[byte as usize] += 1;
+        if self.counts[byte as usize] == 1 {
+            self.distinct += 1;
+        }
+
+        if self.recent.len() > self.window {
+            let removed = self.recent.pop_front().unwrap() as usize;
+            self.counts[removed] -= 1;
+            if self.counts[removed] == 0 {
+                self.distinct -= 1;
+            }
+        }
+
+        self.recent.len() == self.window && self.distinct == self.window
+    }
 }
 
 fn main() {
     let args = Args::parse();
 
-    let data = std::fs::read_to_string(&args.data_file).expect("Failed to open file");
+    let windows = if args.windows.is_empty() {
+        // The start of a packet is four different characters and the start of a message is 14
+        vec![4, 14]
+    } else {
+        args.windows
+    };
 
-    // In the protocol being used by the Elves, the start of a packet is indicated by a sequence of four characters that are all different
-    let mut last_four: VecDeque<char> = data.chars().take(4).collect();
-    for (index, c) in data.chars().enumerate().skip(4) {
-        if is_unique(&last_four) {
-            println!("Index at {}", index);
-            break;
-        }
+    if windows.iter().any(|window| *window == 0 || *window > 256) {
+        panic!("Window sizes must be between 1 and 256");
+    }
+
+    if args.buffer_size == 0 {
+        panic!("Buffer size must be at least 1");
+    }
+
+    let source: Box<dyn Read> = match args.data_file.as_deref() {
+        None | Some("-") => Box::new(io::stdin().lock()),
+        Some(data_file) => Box::new(File::open(data_file).expect("Failed to open file")),
+    };
+
+    let mut detectors: Vec<MarkerDetector> = windows.into_iter().map(MarkerDetector::new).collect();
+    scan(
+        source,
+        &mut detectors,
+        args.buffer_size,
+        args.all,
+        |window, offset| {
+            println!("Window {} marker at {}", window, offset);
+            io::stdout().flush().expect("Failed to flush stdout");
+        },
+    );
 
-        last_four.pop_front();
-        last_four.push_back(c);
+    for detector in detectors.iter().filter(|detector| !detector.found) {
+        println!("Window {} marker not found", detector.window);
     }
+}
+
+// Feeds the source through every detector using a fixed-size buffer, calling `on_marker` with the
+// window size and the number of bytes processed at the end of each marker as soon as it is found.
+// Line endings at the very end of the source are not part of the data, so they are held back until
+// something else follows them.
+fn scan(
+    mut source: impl Read,
+    detectors: &mut [MarkerDetector],
+    buffer_size: usize,
+    all: bool,
+    mut on_marker: impl FnMut(usize, u64),
+) {
+    let mut buffer = vec![0; buffer_size];
+    let mut line_ending: Vec<u8> = Vec::new();
+    let mut offset: u64 = 0;
+    loop {
+        let read = match source.read(&mut buffer) {
+            Ok(0) => return,
+            Ok(read) => read,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => panic!("Failed to read data: {}", error),
+        };
+
+        for byte in buffer[..read].iter() {
+            if *byte == b'\n' || *byte == b'\r' {
+                line_ending.push(*byte);
+                continue;
+            }
 
-    // For part 2, use 14 chars
-    let mut last_fourteen: VecDeque<char> = data.chars().take(14).collect();
-    for (index, c) in data.chars().enumerate().skip(14) {
-        if is_unique(&last_fourteen) {
-            println!("Index at {}", index);
-            break;
+            for byte in line_ending.drain(..).chain([*byte]) {
+                offset += 1;
+                for detector in detectors.iter_mut() {
+                    if detector.found && !all {
+                        continue;
+                    }
+
+                    if detector.push(byte) {
+                        detector.found = true;
+                        on_marker(detector.window, offset);
+                    }
+                }
+            }
         }
 
-        last_fourteen.pop_front();
-        last_fourteen.push_back(c);
+        if !all && detectors.iter().all(|detector| detector.found) {
+            return;
+        }
     }
 }
 
-fn is_unique(maybe_unique: &VecDeque<char>) -> bool {
-    let mut set = HashSet::new();
-    maybe_unique.iter().for_each(|c| {
-        set.insert(*c);
-    });
-    set.len() == maybe_unique.len()
+// Returns the number of bytes processed at the end of the first marker of the given window size,
+// for callers which only need one marker rather than every window reported as it is found
+#[allow(dead_code)]
+fn find_marker(source: impl Read, window: usize, buffer_size: usize) -> Option<u64> {
+    let mut marker = None;
+    scan(
+        source,
+        &mut [MarkerDetector::new(window)],
+        buffer_size,
+        false,
+        |_, offset| marker = Some(offset),
+    );
+
+    marker
 }
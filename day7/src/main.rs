@@ -1,9 +1,11 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::{
-    cell::RefCell,
-    fs::File,
+    cell::OnceCell,
+    collections::{HashMap, HashSet},
+    fs::{self, File},
     io::{BufRead, BufReader},
-    rc::Rc,
+    path::{Path, PathBuf},
+    process,
 };
 
 #[derive(Parser, Debug)]
@@ -11,19 +13,225 @@ use std::{
 struct Args {
     #[arg(long)]
     data_file: String,
+    /// Total size of the disk in bytes
+    #[arg(long, default_value_t = 70000000)]
+    disk_size: u64,
+    /// Free space in bytes needed to run the update
+    #[arg(long, default_value_t = 30000000)]
+    required_free_space: u64,
+    /// Also find the set of directories which frees the fewest bytes while still freeing enough
+    #[arg(long)]
+    minimal_deletions: bool,
+    #[command(subcommand)]
+    query: Option<Query>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Query {
+    /// Print the total size of each directory, deepest first
+    Du {
+        #[arg(long)]
+        max_depth: Option<usize>,
+    },
+    /// Print files and directories matching all of the given filters
+    Find {
+        #[arg(long)]
+        min_size: Option<u64>,
+        #[arg(long)]
+        max_size: Option<u64>,
+        /// Glob matched against the entry name, supports * and ?
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Print the largest files
+    Largest {
+        #[arg(long, default_value_t = 10)]
+        count: usize,
+    },
+    /// Print the whole tree as either "json" or a "find" style listing of type, size and path
+    Export {
+        #[arg(long, default_value = "json")]
+        format: String,
+    },
+    /// Recreate the tree on disk using sparse files, in a new temporary directory unless a target
+    /// is given
+    Materialise {
+        #[arg(long)]
+        target: Option<String>,
+    },
 }
 
 struct SantaFile {
     name: String,
-    size: u32,
+    size: u64,
+    // Transcript line the file was first listed on
+    line: usize,
 }
 
-#[derive(Default)]
+// Directories refer to each other by their index in the FileSystem arena
+type DirectoryId = usize;
+
 struct Directory {
     name: String,
-    sub_directories: Vec<Rc<RefCell<Directory>>>,
+    parent: Option<DirectoryId>,
+    sub_directories: Vec<DirectoryId>,
+    sub_directory_lookup: HashMap<String, DirectoryId>,
     files: Vec<SantaFile>,
-    size: u32,
+    file_lookup: HashMap<String, usize>,
+    // Transcript line the directory was first seen on
+    line: usize,
+}
+
+struct FileSystem {
+    directories: Vec<Directory>,
+    // Total size of each directory including everything below it, calculated on first use
+    total_sizes: OnceCell<Vec<u64>>,
+}
+
+impl FileSystem {
+    const ROOT: DirectoryId = 0;
+
+    fn new() -> FileSystem {
+        FileSystem {
+            directories: vec![Directory {
+                name: "/".to_string(),
+                parent: None,
+                sub_directories: Vec::new(),
+                sub_directory_lookup: HashMap::new(),
+                files: Vec::new(),
+                file_lookup: HashMap::new(),
+                line: 0,
+            }],
+            total_sizes: OnceCell::new(),
+        }
+    }
+
+    fn directory(&self, id: DirectoryId) -> &Directory {
+        &self.directories[id]
+    }
+
+    fn directory_ids(&self) -> impl DoubleEndedIterator<Item = DirectoryId> {
+        0..self.directories.len()
+    }
+
+    fn parent(&self, id: DirectoryId) -> Option<DirectoryId> {
+        self.directories[id].parent
+    }
+
+    fn get_or_create_subdir(
+        &mut self,
+        parent: DirectoryId,
+        name: &str,
+        line: usize,
+    ) -> Result<DirectoryId, String> {
+        let parent_directory = &self.directories[parent];
+        if let Some(id) = parent_directory.sub_directory_lookup.get(name) {
+            return Ok(*id);
+        }
+
+        if let Some(file) = parent_directory.file_lookup.get(name) {
+            return Err(format!(
+                "{} is used as a directory but was listed as a file on line {}",
+                join_path(&self.path(parent), name),
+                parent_directory.files[*file].line
+            ));
+        }
+
+        self.total_sizes.take();
+        let id = self.directories.len();
+        self.directories.push(Directory {
+            name: name.to_string(),
+            parent: Some(parent),
+            sub_directories: Vec::new(),
+            sub_directory_lookup: HashMap::new(),
+            files: Vec::new(),
+            file_lookup: HashMap::new(),
+            line,
+        });
+
+        let parent = &mut self.directories[parent];
+        parent.sub_directories.push(id);
+        parent.sub_directory_lookup.insert(name.to_string(), id);
+
+        Ok(id)
+    }
+
+    // Listing the same file again is ignored as long as it has the same size
+    fn add_file(
+        &mut self,
+        directory: DirectoryId,
+        name: &str,
+        size: u64,
+        line: usize,
+    ) -> Result<(), String> {
+        let parent_directory = &self.directories[directory];
+        if let Some(subdir) = parent_directory.sub_directory_lookup.get(name) {
+            return Err(format!(
+                "{} is listed as a file but was seen as a directory on line {}",
+                join_path(&self.path(directory), name),
+                self.directories[*subdir].line
+            ));
+        }
+
+        if let Some(file) = parent_directory.file_lookup.get(name) {
+            let file = &parent_directory.files[*file];
+            if file.size != size {
+                return Err(format!(
+                    "{} is listed with size {} but was listed with size {} on line {}",
+                    join_path(&self.path(directory), name),
+                    size,
+                    file.size,
+                    file.line
+                ));
+            }
+
+            return Ok(());
+        }
+
+        self.total_sizes.take();
+        let directory = &mut self.directories[directory];
+        directory
+            .file_lookup
+            .insert(name.to_string(), directory.files.len());
+        directory.files.push(SantaFile {
+            name: name.to_string(),
+            size,
+            line,
+        });
+
+        Ok(())
+    }
+
+    fn size(&self, id: DirectoryId) -> u64 {
+        self.total_sizes.get_or_init(|| {
+            let mut sizes: Vec<u64> = self
+                .directories
+                .iter()
+                .map(|d| d.files.iter().map(|f| f.size).sum())
+                .collect();
+
+            // Children are always created after their parent so walking backwards visits every
+            // directory before its parent.
+            for id in (1..self.directories.len()).rev() {
+                let parent = self.directories[id].parent.unwrap();
+                sizes[parent] += sizes[id];
+            }
+
+            sizes
+        })[id]
+    }
+
+    fn path(&self, id: DirectoryId) -> String {
+        let mut names = Vec::new();
+        let mut current = id;
+        while let Some(parent) = self.parent(current) {
+            names.push(self.directories[current].name.as_str());
+            current = parent;
+        }
+        names.reverse();
+
+        format!("/{}", names.join("/"))
+    }
 }
 
 fn main() {
@@ -32,148 +240,478 @@ fn main() {
     let file = File::open(&args.data_file).expect("Failed to open file");
     let reader = BufReader::new(file);
 
-    let root = Rc::new(RefCell::new(Directory {
-        name: "/".to_string(),
-        ..Default::default()
-    }));
-    let mut breadcrumbs = vec![root.clone()];
-
     let lines: Vec<String> = reader.lines().map(|line| line.unwrap()).collect();
+    let (file_system, warnings) = parse_transcript(&lines);
+    for warning in warnings.iter() {
+        eprintln!("Warning: {}", warning);
+    }
+
+    if let Some(query) = args.query {
+        run_query(&query, &file_system);
+        return;
+    }
+
+    print_directory(&file_system, FileSystem::ROOT, 0);
+    let total_under_limit: u64 = file_system
+        .directory_ids()
+        .map(|id| file_system.size(id))
+        .filter(|s| *s <= 100000)
+        .sum();
+    println!("Sum of directories <= 100000: {}", total_under_limit);
+
+    // Part 2:
+    let used_space = file_system.size(FileSystem::ROOT);
+    let current_free_space = args.disk_size.checked_sub(used_space).unwrap_or_else(|| {
+        panic!(
+            "Used space {} is larger than the disk size {}",
+            used_space, args.disk_size
+        )
+    });
+    let required_additional_free_space =
+        match args.required_free_space.checked_sub(current_free_space) {
+            Some(0) | None => {
+                println!(
+                    "Already have {} free which is at least the {} required",
+                    current_free_space, args.required_free_space
+                );
+                return;
+            }
+            Some(required) => required,
+        };
+    if required_additional_free_space > used_space {
+        eprintln!(
+            "Cannot free {} bytes even by deleting /, which only frees {}",
+            required_additional_free_space, used_space
+        );
+        process::exit(1);
+    }
+
+    let (id, space_to_free) = file_system
+        .directory_ids()
+        .map(|id| (id, file_system.size(id)))
+        .filter(|(_, s)| *s >= required_additional_free_space)
+        .min_by_key(|(_, s)| *s)
+        .expect("Deleting / always frees enough space");
+    println!(
+        "Will need to free directory {} of size {}",
+        file_system.path(id),
+        space_to_free
+    );
+
+    if !args.minimal_deletions {
+        return;
+    }
+
+    let deletions = find_minimal_deletions(&file_system, required_additional_free_space)
+        .expect("Deleting / always frees enough space");
+    let freed: u64 = deletions.iter().map(|id| file_system.size(*id)).sum();
+    println!(
+        "Fewest bytes freed by deleting any set of directories is {} using {} directories:",
+        freed,
+        deletions.len()
+    );
+    for id in deletions {
+        println!("  {} (size={})", file_system.path(id), file_system.size(id));
+    }
+}
+
+// Finds the directories to delete which free at least `required` while freeing as few bytes as
+// possible. Deleting a directory also deletes everything below it, so a directory and its
+// descendants are never chosen together.
+//
+// Directories are visited in pre-order. At each position a directory can either be deleted,
+// jumping past the end of its subtree, or skipped, moving on to the next position (its first child
+// if it has one). This is searched depth first, trying deletions first so that a good answer is
+// found early, and a branch is abandoned once it can no longer beat the best answer so far: either
+// it already frees more, or deleting everything left would still not free enough. A position and
+// amount freed always lead to the same outcomes, so each pair is only searched once. The work
+// depends on the number of directories and the sizes that occur, not on the number of bytes.
+fn find_minimal_deletions(file_system: &FileSystem, required: u64) -> Option<Vec<DirectoryId>> {
+    let (order, subtree_end) = pre_order(file_system);
+    let sizes: Vec<u64> = order.iter().map(|id| file_system.size(*id)).collect();
+
+    // The most that can be freed using only the directories from each position onwards, which is
+    // found by deleting every directory whose parent comes before that position
+    let mut most_freeable = vec![0; order.len() + 1];
+    for position in (0..order.len()).rev() {
+        most_freeable[position] = sizes[position] + most_freeable[subtree_end[position]];
+    }
+
+    // Deletions made so far on each branch, stored as a position and the previous deletion
+    let mut deletions: Vec<(usize, Option<usize>)> = Vec::new();
+    let mut searched: HashSet<(usize, u64)> = HashSet::new();
+    // The smallest amount freed which reaches `required` and its last deletion
+    let mut best: Option<(u64, usize)> = None;
+
+    // Each entry is a position, the amount freed before it and the last deletion made
+    let mut to_search: Vec<(usize, u64, Option<usize>)> = vec![(0, 0, None)];
+    while let Some((position, freed, last_deletion)) = to_search.pop() {
+        if best.is_some_and(|(best_freed, _)| best_freed == required) {
+            break;
+        }
+        if position == order.len()
+            || freed + most_freeable[position] < required
+            || !searched.insert((position, freed))
+        {
+            continue;
+        }
+
+        // Pushed first so that deleting is searched first
+        to_search.push((position + 1, freed, last_deletion));
+
+        let deleted = freed + sizes[position];
+        if best.is_some_and(|(best_freed, _)| deleted >= best_freed) {
+            continue;
+        }
+        deletions.push((position, last_deletion));
+        if deleted >= required {
+            best = Some((deleted, deletions.len() - 1));
+        } else {
+            to_search.push((subtree_end[position], deleted, Some(deletions.len() - 1)));
+        }
+    }
+
+    let (_, mut deletion) = best?;
+    let mut chosen = Vec::new();
+    loop {
+        let (position, previous) = deletions[deletion];
+        chosen.push(order[position]);
+        match previous {
+            Some(previous) => deletion = previous,
+            None => break,
+        }
+    }
+    chosen.sort();
+
+    Some(chosen)
+}
+
+// Returns the directories in pre-order along with the position just past the end of each
+// directory's subtree
+fn pre_order(file_system: &FileSystem) -> (Vec<DirectoryId>, Vec<usize>) {
+    let mut order = Vec::new();
+    let mut subtree_end = Vec::new();
+    // Each entry is a directory and whether its subtree has already been visited
+    let mut to_process = vec![(FileSystem::ROOT, false)];
+    let mut open_positions = Vec::new();
+    while let Some((id, visited)) = to_process.pop() {
+        if visited {
+            let position: usize = open_positions.pop().unwrap();
+            subtree_end[position] = order.len();
+            continue;
+        }
+
+        open_positions.push(order.len());
+        order.push(id);
+        subtree_end.push(0);
+        to_process.push((id, true));
+        for subdir in file_system.directory(id).sub_directories.iter().rev() {
+            to_process.push((*subdir, false));
+        }
+    }
+
+    (order, subtree_end)
+}
+
+// Rebuilds the file system from a terminal transcript. Problems with the transcript are returned
+// as warnings prefixed with their line number and the offending line is skipped.
+fn parse_transcript(lines: &[String]) -> (FileSystem, Vec<String>) {
+    let mut file_system = FileSystem::new();
+    let mut current_directory = FileSystem::ROOT;
+    let mut warnings = Vec::new();
     let mut index = 0;
 
     while index < lines.len() {
         let line = lines.get(index).unwrap();
-        if line.starts_with("$") {
-            let components: Vec<&str> = line.split_whitespace().collect();
-            match *components.get(1).unwrap() {
-                "cd" => {
-                    let directory_name = components.get(2).unwrap();
-                    if *directory_name == "/" {
-                        breadcrumbs.clear();
-                        breadcrumbs.push(root.clone());
-                    } else if *directory_name == ".." {
-                        breadcrumbs.pop();
-                    } else {
-                        let current_directory = breadcrumbs.last().unwrap();
-                        breadcrumbs.push(get_or_create_subdir(
-                            current_directory.clone(),
-                            directory_name,
-                        ));
-                    }
+        let line_number = index + 1;
+        let mut warn =
+            |message: String| warnings.push(format!("line {}: {}", line_number, message));
 
-                    // println!(
-                    //     "Entering {}",
-                    //     breadcrumbs
-                    //         .iter()
-                    //         .map(|d| d.borrow().name.clone())
-                    //         .reduce(|acc, d| acc + "/" + &d)
-                    //         .unwrap()
-                    // );
+        let components: Vec<&str> = line.split_whitespace().collect();
+        match components.as_slice() {
+            ["$", "cd", "/"] => current_directory = FileSystem::ROOT,
+            ["$", "cd", ".."] => match file_system.parent(current_directory) {
+                Some(parent) => current_directory = parent,
+                None => warn("cd .. from the root directory, staying in /".to_string()),
+            },
+            ["$", "cd", directory_name] => {
+                match file_system.get_or_create_subdir(
+                    current_directory,
+                    directory_name,
+                    line_number,
+                ) {
+                    Ok(id) => current_directory = id,
+                    Err(error) => warn(error),
                 }
-                "ls" => {
-                    // All lines until a $ line are file listings
-                    let current_directory = breadcrumbs.last().unwrap().clone();
-                    while index + 1 < lines.len() && !lines.get(index + 1).unwrap().starts_with("$")
-                    {
-                        index += 1;
-                        let (size, name) = lines.get(index).unwrap().split_once(" ").unwrap();
-                        if size == "dir" {
-                            get_or_create_subdir(current_directory.clone(), name);
-                        } else {
-                            let file_size: u32 = size.parse().unwrap();
-                            current_directory.borrow_mut().files.push(SantaFile {
-                                name: name.to_string(),
-                                size: file_size,
-                            });
-                            breadcrumbs
-                                .iter_mut()
-                                .for_each(|b| b.borrow_mut().size += file_size);
-                        }
+            }
+            ["$", "ls"] => {
+                // All lines until a $ line are file listings
+                while index + 1 < lines.len() && !lines.get(index + 1).unwrap().starts_with("$") {
+                    index += 1;
+                    let line_number = index + 1;
+                    let listing = lines.get(index).unwrap();
+                    let result = match listing.split_once(' ') {
+                        Some(("dir", name)) => file_system
+                            .get_or_create_subdir(current_directory, name, line_number)
+                            .map(|_| ()),
+                        Some((size, name)) => match size.parse() {
+                            Ok(size) => {
+                                file_system.add_file(current_directory, name, size, line_number)
+                            }
+                            Err(_) => Err(format!("invalid file size in \"{}\"", listing)),
+                        },
+                        None => Err(format!("invalid listing \"{}\"", listing)),
+                    };
+
+                    if let Err(error) = result {
+                        warnings.push(format!("line {}: {}", line_number, error));
                     }
                 }
-                &_ => panic!("Unexpected operation"),
             }
+            ["$", ..] => {
+                warn(format!("unknown command \"{}\", ignoring its output", line));
+                while index + 1 < lines.len() && !lines.get(index + 1).unwrap().starts_with("$") {
+                    index += 1;
+                }
+            }
+            [] => {}
+            _ => warn(format!("output \"{}\" without a command", line)),
         }
 
         index += 1;
     }
 
-    print_directory(&root.borrow(), 0);
-    let total_under_limit: u32 = get_all_directories(root.clone())
-        .iter()
-        .map(|d| d.borrow().size)
-        .filter(|s| *s <= 100000)
-        .sum();
-    println!("Sum of directories <= 100000: {}", total_under_limit);
+    (file_system, warnings)
+}
 
-    // Part 2:
-    let total_drive_size = 70000000;
-    let required_free_space = 30000000;
-    let current_free_space = total_drive_size - root.borrow().size;
-    let required_additional_free_space = required_free_space - current_free_space;
+fn run_query(query: &Query, file_system: &FileSystem) {
+    match query {
+        Query::Du { max_depth } => print_disk_usage(file_system, *max_depth),
+        Query::Find {
+            min_size,
+            max_size,
+            name,
+        } => {
+            let matches = |entry_name: &str, size: u64| {
+                min_size.is_none_or(|min| size >= min)
+                    && max_size.is_none_or(|max| size <= max)
+                    && name
+                        .as_ref()
+                        .is_none_or(|pattern| glob_match(pattern, entry_name))
+            };
+
+            for id in file_system.directory_ids() {
+                let directory = file_system.directory(id);
+                let path = file_system.path(id);
+                if matches(&directory.name, file_system.size(id)) {
+                    println!("{} (dir, size={})", path, file_system.size(id));
+                }
+
+                for file in directory.files.iter() {
+                    if matches(&file.name, file.size) {
+                        println!(
+                            "{} (file, size={})",
+                            join_path(&path, &file.name),
+                            file.size
+                        );
+                    }
+                }
+            }
+        }
+        Query::Largest { count } => {
+            let mut files: Vec<(DirectoryId, &SantaFile)> = file_system
+                .directory_ids()
+                .flat_map(|id| file_system.directory(id).files.iter().map(move |f| (id, f)))
+                .collect();
+            files.sort_by_key(|(_, file)| std::cmp::Reverse(file.size));
 
-    let space_to_free = get_all_directories(root.clone())
-        .iter()
-        .map(|d| d.borrow().size)
-        .filter(|s| *s >= required_additional_free_space)
-        .min()
-        .unwrap();
-    println!("Will need to free a directory of size {}", space_to_free);
+            for (id, file) in files.iter().take(*count) {
+                println!(
+                    "{}\t{}",
+                    file.size,
+                    join_path(&file_system.path(*id), &file.name)
+                );
+            }
+        }
+        Query::Export { format } => match format.as_str() {
+            "json" => {
+                let mut json = String::new();
+                write_json(file_system, FileSystem::ROOT, &mut json);
+                println!("{}", json);
+            }
+            "find" => print_find_listing(file_system),
+            _ => panic!("Unknown export format: {}", format),
+        },
+        Query::Materialise { target } => {
+            let target = match target {
+                Some(target) => PathBuf::from(target),
+                None => std::env::temp_dir().join(format!("day7-{}", process::id())),
+            };
+            materialise(file_system, &target);
+            println!("{}", target.display());
+        }
+    }
 }
 
-fn get_all_directories(root: Rc<RefCell<Directory>>) -> Vec<Rc<RefCell<Directory>>> {
-    let mut results = Vec::new();
-    let mut to_process = vec![root];
+fn write_json(file_system: &FileSystem, id: DirectoryId, json: &mut String) {
+    let directory = file_system.directory(id);
+    json.push_str(&format!(
+        "{{\"name\":{},\"type\":\"dir\",\"size\":{},\"children\":[",
+        json_string(&directory.name),
+        file_system.size(id)
+    ));
+
+    for (index, subdir) in directory.sub_directories.iter().enumerate() {
+        if index > 0 {
+            json.push(',');
+        }
+        write_json(file_system, *subdir, json);
+    }
 
-    while !to_process.is_empty() {
-        let directory = to_process.pop().unwrap();
-        to_process.append(&mut directory.borrow().sub_directories.clone());
-        results.push(directory.clone());
+    for (index, file) in directory.files.iter().enumerate() {
+        if index > 0 || !directory.sub_directories.is_empty() {
+            json.push(',');
+        }
+        json.push_str(&format!(
+            "{{\"name\":{},\"type\":\"file\",\"size\":{}}}",
+            json_string(&file.name),
+            file.size
+        ));
     }
 
-    results
+    json.push_str("]}");
 }
 
-fn print_directory(directory: &Directory, space_depth: u32) {
-    for _ in 0..space_depth {
-        print!(" ");
+fn json_string(value: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
     }
-    print!("- {} (dir)\n", directory.name);
-    for subdir in directory.sub_directories.iter() {
-        print_directory(&subdir.borrow(), space_depth + 2);
+    escaped.push('"');
+
+    escaped
+}
+
+// Lists every entry with its type, size and path. Files can be compared against
+// `find <dir> -type f -printf "f %s /%P\n"` on a materialised tree.
+fn print_find_listing(file_system: &FileSystem) {
+    let mut to_process = vec![FileSystem::ROOT];
+    while let Some(id) = to_process.pop() {
+        let directory = file_system.directory(id);
+        let path = file_system.path(id);
+        println!("d {} {}", file_system.size(id), path);
+        for file in directory.files.iter() {
+            println!("f {} {}", file.size, join_path(&path, &file.name));
+        }
+
+        to_process.extend(directory.sub_directories.iter().rev());
     }
+}
 
-    for file in directory.files.iter() {
-        for _ in 0..(space_depth + 2) {
-            print!(" ");
+fn materialise(file_system: &FileSystem, target: &Path) {
+    let mut to_process = vec![(FileSystem::ROOT, target.to_path_buf())];
+    while let Some((id, path)) = to_process.pop() {
+        fs::create_dir_all(&path).expect("Failed to create directory");
+
+        let directory = file_system.directory(id);
+        for file in directory.files.iter() {
+            if !is_safe_name(&file.name) {
+                eprintln!("Warning: skipping file with unsafe name {}", file.name);
+                continue;
+            }
+
+            // Setting the length without writing anything leaves a sparse file
+            File::create(path.join(&file.name))
+                .and_then(|f| f.set_len(file.size))
+                .expect("Failed to create file");
+        }
+
+        for subdir in directory.sub_directories.iter() {
+            let name = &file_system.directory(*subdir).name;
+            if !is_safe_name(name) {
+                eprintln!("Warning: skipping directory with unsafe name {}", name);
+                continue;
+            }
+
+            to_process.push((*subdir, path.join(name)));
         }
-        print!("- {} (file, size={})\n", file.name, file.size);
     }
 }
 
-fn get_or_create_subdir(
-    parent_directory: Rc<RefCell<Directory>>,
-    subdir_name: &str,
-) -> Rc<RefCell<Directory>> {
-    let subdir = parent_directory
-        .borrow()
-        .sub_directories
-        .iter()
-        .find(|dir| dir.borrow().name == subdir_name)
-        .cloned();
+// Names from the transcript must not be able to escape the target directory
+fn is_safe_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains('/') && !name.contains('\\')
+}
+
+// Prints directories after their children, like du
+fn print_disk_usage(file_system: &FileSystem, max_depth: Option<usize>) {
+    // Each entry is a directory, its depth and whether its children have already been queued
+    let mut to_process = vec![(FileSystem::ROOT, 0, false)];
+    while let Some((id, depth, expanded)) = to_process.pop() {
+        if expanded {
+            println!("{}\t{}", file_system.size(id), file_system.path(id));
+            continue;
+        }
 
-    if let Some(subdir) = subdir {
-        return subdir.clone();
+        to_process.push((id, depth, true));
+        if max_depth.is_none_or(|max_depth| depth < max_depth) {
+            for subdir in file_system.directory(id).sub_directories.iter().rev() {
+                to_process.push((*subdir, depth + 1, false));
+            }
+        }
     }
+}
 
-    let new_dir = Rc::new(RefCell::new(Directory {
-        name: subdir_name.to_string(),
-        ..Default::default()
-    }));
-    parent_directory
-        .borrow_mut()
-        .sub_directories
-        .push(new_dir.clone());
+fn join_path(parent: &str, name: &str) -> String {
+    if parent.ends_with('/') {
+        return format!("{}{}", parent, name);
+    }
 
-    new_dir
+    format!("{}/{}", parent, name)
+}
+
+// Matches a glob where * matches any number of characters and ? matches exactly one
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // matched[j] is true if the pattern so far matches the first j characters of the name
+    let mut matched = vec![false; name.len() + 1];
+    matched[0] = true;
+    for p in pattern.iter() {
+        let mut next = vec![false; name.len() + 1];
+        for j in 0..=name.len() {
+            next[j] = match p {
+                '*' => matched[j] || (j > 0 && next[j - 1]),
+                '?' => j > 0 && matched[j - 1],
+                c => j > 0 && matched[j - 1] && name[j - 1] == *c,
+            };
+        }
+        matched = next;
+    }
+
+    matched[name.len()]
+}
+
+fn print_directory(file_system: &FileSystem, id: DirectoryId, space_depth: u32) {
+    let directory = file_system.directory(id);
+    for _ in 0..space_depth {
+        print!(" ");
+    }
+    println!("- {} (dir)", directory.name);
+    for subdir in directory.sub_directories.iter() {
+        print_directory(file_system, *subdir, space_depth + 2);
+    }
+
+    for file in directory.files.iter() {
+        for _ in 0..(space_depth + 2) {
+            print!(" ");
+        }
+        println!("- {} (file, size={})", file.name, file.size);
+    }
 }
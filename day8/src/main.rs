@@ -1,6 +1,6 @@
 use clap::Parser;
 use std::{
-    fs::File,
+    fs::{self, File},
     io::{BufRead, BufReader},
 };
 
@@ -9,171 +9,348 @@ use std::{
 struct Args {
     #[arg(long)]
     data_file: String,
+    /// Print ASCII heatmaps of visibility and scenic score
+    #[arg(long)]
+    ascii_heatmap: bool,
+    /// Write PPM heatmaps to <prefix>-visibility.ppm and <prefix>-scenic.ppm
+    #[arg(long)]
+    image_prefix: Option<String>,
+    /// Directions to look in, "cardinal", "diagonal", "all" or a comma separated list of names
+    /// (left, up-right, ...) and custom steps written as dx:dy. Defaults to cardinal, or all when
+    /// looking from a viewpoint.
+    #[arg(long)]
+    directions: Option<String>,
+    /// Report the trees visible from this position, written as x,y
+    #[arg(long)]
+    viewpoint: Option<String>,
+    /// Eye height of the observer at the viewpoint, defaults to the height of the tree there
+    #[arg(long)]
+    observer_height: Option<i64>,
+}
+
+// Tree heights stored row by row
+struct Forest {
+    width: usize,
+    height: usize,
+    trees: Vec<u8>,
 }
 
+impl Forest {
+    fn index(&self, x: usize, y: usize) -> usize {
+        y * self.width + x
+    }
+
+    // Returns the position one step in `direction`, if it is inside the forest
+    fn step(&self, (x, y): (usize, usize), (dx, dy): (i64, i64)) -> Option<(usize, usize)> {
+        let x = x.checked_add_signed(dx as isize)?;
+        let y = y.checked_add_signed(dy as isize)?;
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        Some((x, y))
+    }
+}
+
+// The result of looking from each tree in a single direction
+struct DirectionView {
+    // Whether every tree between this tree and the edge is shorter
+    visible: Vec<bool>,
+    // How many trees can be seen before being blocked or reaching the edge
+    viewing_distance: Vec<usize>,
+}
+
+const CARDINAL_DIRECTIONS: [(&str, (i64, i64)); 4] = [
+    ("left", (-1, 0)),
+    ("right", (1, 0)),
+    ("up", (0, -1)),
+    ("down", (0, 1)),
+];
+
+const DIAGONAL_DIRECTIONS: [(&str, (i64, i64)); 4] = [
+    ("up-left", (-1, -1)),
+    ("up-right", (1, -1)),
+    ("down-left", (-1, 1)),
+    ("down-right", (1, 1)),
+];
+
+// Characters used for ASCII heatmaps from lowest to highest
+const SHADES: &[u8] = b" .:-=+*#%@";
+
 fn main() {
     let args = Args::parse();
 
     let file = File::open(&args.data_file).expect("Failed to open file");
     let reader = BufReader::new(file);
-    let data: Vec<Vec<i16>> = reader
+    let rows: Vec<Vec<u8>> = reader
         .lines()
         .map(|line| line.expect("Failed to parse line"))
         .map(|line| {
             line.chars()
-                .map(|c| c.to_string().parse::<i16>().expect("Failed to parse value"))
+                .map(|c| c.to_digit(10).expect("Failed to parse value") as u8)
                 .collect()
         })
         .collect();
 
+    let width = rows.first().map(|row| row.len()).unwrap_or(0);
+    if rows.iter().any(|row| row.len() != width) {
+        panic!("Every row must be the same width");
+    }
+    let forest = Forest {
+        width,
+        height: rows.len(),
+        trees: rows.into_iter().flatten().collect(),
+    };
+
+    if let Some(viewpoint) = args.viewpoint {
+        let (x, y) = viewpoint
+            .split_once(',')
+            .expect("Viewpoint must be written as x,y");
+        let x: usize = x.trim().parse().expect("Failed to parse x");
+        let y: usize = y.trim().parse().expect("Failed to parse y");
+        if x >= forest.width || y >= forest.height {
+            panic!("Viewpoint ({}, {}) is outside the forest", x, y);
+        }
+
+        let observer_height = args
+            .observer_height
+            .unwrap_or(i64::from(forest.trees[forest.index(x, y)]));
+        let directions = parse_directions(args.directions.as_deref().unwrap_or("all"));
+        let visible = visible_from(&forest, (x, y), observer_height, &directions);
+        println!(
+            "Trees visible from ({}, {}) at height {}: {}",
+            x,
+            y,
+            observer_height,
+            visible.iter().filter(|v| **v).count()
+        );
+        print_visible_from(&forest, (x, y), &visible);
+        return;
+    }
+
+    let directions = parse_directions(args.directions.as_deref().unwrap_or("cardinal"));
+    let views: Vec<DirectionView> = directions
+        .iter()
+        .map(|(_, direction)| look(&forest, *direction))
+        .collect();
+
     // Part 1 is how many trees are visible from the edge
-    let left_visible_table = get_left_visible(&data);
-    let right_visible_table = get_right_visible(&data);
-    let top_visible_table = transpose(&get_left_visible(&transpose(&data)));
-    let bottom_visible_table = transpose(&get_right_visible(&transpose(&data)));
-
-    let tables = vec![
-        &left_visible_table,
-        &right_visible_table,
-        &top_visible_table,
-        &bottom_visible_table,
-    ];
-
-    // Now zip the above tables to get the total number of visible trees
-    let number_of_visibile_trees: usize = (0..left_visible_table.len())
-        .map(|x| {
-            let x = x;
-            (0..left_visible_table[0].len())
-                .map(|y| tables.iter().any(|t| t[x][y]))
-                .filter(|v| *v)
-                .count()
-        })
-        .sum();
+    let visible_directions: Vec<usize> = (0..forest.trees.len())
+        .map(|index| views.iter().filter(|view| view.visible[index]).count())
+        .collect();
+    let number_of_visibile_trees = visible_directions.iter().filter(|v| **v > 0).count();
 
     println!("Number of visible trees: {}", number_of_visibile_trees);
 
     // Part 2 is finding the highest scenic score
-    let left_scenic_table = get_left_scenic(&data);
-    let right_scenic_table = get_left_scenic(
-        &data
-            .iter()
-            .map(|row| row.iter().rev().cloned().collect())
-            .collect(),
-    )
-    .iter()
-    .map(|row| row.iter().rev().cloned().collect())
-    .collect();
-    let top_scenic_table = transpose(&get_left_scenic(&transpose(&data)));
-    let bottom_scenic_table = transpose(
-        &get_left_scenic(
-            &transpose(&data)
+    let scenic_scores: Vec<usize> = (0..forest.trees.len())
+        .map(|index| {
+            views
                 .iter()
-                .map(|row| row.iter().rev().cloned().collect())
-                .collect(),
-        )
-        .iter()
-        .map(|row| row.iter().rev().cloned().collect())
-        .collect(),
-    );
-    let scenic_tables = vec![
-        &left_scenic_table,
-        &right_scenic_table,
-        &top_scenic_table,
-        &bottom_scenic_table,
-    ];
-    let best_scenary_score = (0..left_scenic_table.len())
-        .flat_map(|x| {
-            let x = &x;
-            (0..left_scenic_table[0].len())
-                .map(|y| {
-                    let x = *x;
-                    scenic_tables
-                        .iter()
-                        .map(|t| t[x][y])
-                        .reduce(|acc, score| acc * score)
-                        .unwrap()
-                })
-                .collect::<Vec<_>>()
+                .map(|view| view.viewing_distance[index])
+                .product::<usize>()
         })
-        .max()
-        .unwrap();
+        .collect();
+
+    // max_by_key returns the last maximum, so reverse to report the first tree in reading order
+    if let Some((best_index, best_scenary_score)) = scenic_scores
+        .iter()
+        .enumerate()
+        .rev()
+        .max_by_key(|(_, score)| **score)
+    {
+        println!(
+            "Best scenic score: {} at ({}, {})",
+            best_scenary_score,
+            best_index % forest.width,
+            best_index / forest.width
+        );
+        for ((name, _), view) in directions.iter().zip(views.iter()) {
+            println!("  {}: {}", name, view.viewing_distance[best_index]);
+        }
+    }
 
-    println!("Best scenic score: {}", best_scenary_score);
+    if args.ascii_heatmap {
+        println!("Visibility (number of directions the tree can be seen from):");
+        print_ascii_heatmap(&forest, &visible_directions);
+        println!("Scenic score:");
+        print_ascii_heatmap(&forest, &scenic_scores);
+    }
+
+    if let Some(image_prefix) = args.image_prefix {
+        write_heatmap_image(
+            &forest,
+            &visible_directions,
+            &format!("{}-visibility.ppm", image_prefix),
+        );
+        write_heatmap_image(
+            &forest,
+            &scenic_scores,
+            &format!("{}-scenic.ppm", image_prefix),
+        );
+    }
 }
 
-fn get_left_scenic(data: &Vec<Vec<i16>>) -> Vec<Vec<usize>> {
-    data.iter()
-        .map(|row| {
-            row.iter()
-                .enumerate()
-                .map(|(index, tree)| {
-                    if index == 0 {
-                        return 0;
-                    }
-
-                    let visible_count = row[0..index]
-                        .iter()
-                        .rev()
-                        .take_while(|t| **t < *tree)
-                        .count();
-                    if visible_count != index {
-                        return visible_count + 1;
-                    }
-                    visible_count
-                })
-                .collect::<Vec<usize>>()
-        })
+fn parse_directions(spec: &str) -> Vec<(String, (i64, i64))> {
+    let named = CARDINAL_DIRECTIONS.iter().chain(DIAGONAL_DIRECTIONS.iter());
+    let directions: Vec<(&str, (i64, i64))> = match spec {
+        "cardinal" => CARDINAL_DIRECTIONS.to_vec(),
+        "diagonal" => DIAGONAL_DIRECTIONS.to_vec(),
+        "all" => named.copied().collect(),
+        _ => spec
+            .split(',')
+            .map(|direction| {
+                if let Some((_, step)) = named.clone().find(|(name, _)| *name == direction) {
+                    return (direction, *step);
+                }
+
+                let (dx, dy) = direction
+                    .split_once(':')
+                    .unwrap_or_else(|| panic!("Unknown direction: {}", direction));
+                let dx: i64 = dx.parse().expect("Failed to parse dx");
+                let dy: i64 = dy.parse().expect("Failed to parse dy");
+                if dx == 0 && dy == 0 {
+                    panic!("Direction 0:0 never reaches the edge");
+                }
+
+                (direction, (dx, dy))
+            })
+            .collect(),
+    };
+
+    directions
+        .into_iter()
+        .map(|(name, step)| (name.to_string(), step))
         .collect()
 }
 
-fn get_left_visible(data: &Vec<Vec<i16>>) -> Vec<Vec<bool>> {
-    data.iter()
-        .map(|row| {
-            row.iter()
-                .scan(-1, |tallest, tree| {
-                    if *tallest < *tree {
-                        *tallest = *tree;
-                        Some(true)
-                    } else {
-                        Some(false)
-                    }
-                })
-                .collect()
-        })
-        .collect()
+// Casts a ray from the viewpoint along each direction. A tree is visible if its top is above the
+// line of sight to every tree between it and the observer, so taller trees further away can still
+// be seen over shorter ones.
+fn visible_from(
+    forest: &Forest,
+    viewpoint: (usize, usize),
+    observer_height: i64,
+    directions: &[(String, (i64, i64))],
+) -> Vec<bool> {
+    let mut visible = vec![false; forest.trees.len()];
+    for (_, direction) in directions {
+        // The steepest slope seen so far along the ray, as a rise over a distance
+        let mut steepest: Option<(i64, i64)> = None;
+        let mut distance = 0;
+        let mut position = forest.step(viewpoint, *direction);
+        while let Some((x, y)) = position {
+            distance += 1;
+            let index = forest.index(x, y);
+            let rise = i64::from(forest.trees[index]) - observer_height;
+            let is_steeper = steepest.is_none_or(|(steepest_rise, steepest_distance)| {
+                rise * steepest_distance > steepest_rise * distance
+            });
+            if is_steeper {
+                visible[index] = true;
+                steepest = Some((rise, distance));
+            }
+
+            position = forest.step((x, y), *direction);
+        }
+    }
+
+    visible
 }
 
-fn get_right_visible(data: &Vec<Vec<i16>>) -> Vec<Vec<bool>> {
-    data.iter()
-        .map(|row| {
-            row.iter()
-                .rev()
-                .scan(-1, |tallest, tree| {
-                    if *tallest < *tree {
-                        *tallest = *tree;
-                        Some(true)
-                    } else {
-                        Some(false)
-                    }
-                })
-                .collect::<Vec<bool>>()
-                .iter()
-                .rev()
-                .cloned()
-                .collect()
-        })
-        .collect()
+// Draws the forest with the viewpoint as @, visible trees as their height and hidden trees as .
+fn print_visible_from(forest: &Forest, viewpoint: (usize, usize), visible: &[bool]) {
+    for y in 0..forest.height {
+        let row: String = (0..forest.width)
+            .map(|x| {
+                let index = forest.index(x, y);
+                if (x, y) == viewpoint {
+                    '@'
+                } else if visible[index] {
+                    char::from(b'0' + forest.trees[index])
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        println!("{}", row);
+    }
 }
 
-fn transpose<T>(data: &Vec<Vec<T>>) -> Vec<Vec<T>>
-where
-    T: Clone,
-{
-    (0..data[0].len())
-        .map(|i| {
-            data.iter()
-                .map(|inner| inner[i].clone())
-                .collect::<Vec<T>>()
-        })
-        .collect()
+fn print_ascii_heatmap(forest: &Forest, values: &[usize]) {
+    if forest.width == 0 {
+        return;
+    }
+
+    let max = values.iter().copied().max().unwrap_or(0).max(1);
+    for row in values.chunks(forest.width) {
+        let shaded: String = row
+            .iter()
+            .map(|value| char::from(SHADES[value * (SHADES.len() - 1) / max]))
+            .collect();
+        println!("{}", shaded);
+    }
+}
+
+// Writes a binary PPM, one pixel per tree, shading from dark blue for the lowest values through to
+// yellow for the highest
+fn write_heatmap_image(forest: &Forest, values: &[usize], path: &str) {
+    let max = values.iter().copied().max().unwrap_or(0).max(1);
+    let mut image = format!("P6\n{} {}\n255\n", forest.width, forest.height).into_bytes();
+    for value in values {
+        let level = (value * 255 / max) as u8;
+        image.extend_from_slice(&[level, level / 2, 255 - level]);
+    }
+
+    fs::write(path, image).expect("Failed to write image");
+}
+
+// Works out what every tree can see when looking in `direction`. Each line of trees running in
+// that direction is walked starting from the edge being looked towards, keeping a stack of the
+// trees which could still block the view of the trees further in. Any tree shorter than the
+// current one can never block a later tree, as the current tree is closer and at least as tall, so
+// it is popped. This makes each line linear in its length.
+fn look(forest: &Forest, direction: (i64, i64)) -> DirectionView {
+    let mut visible = vec![false; forest.trees.len()];
+    let mut viewing_distance = vec![0; forest.trees.len()];
+    let backwards = (-direction.0, -direction.1);
+
+    // Lines start at every tree whose next step in `direction` leaves the forest
+    let starts = (0..forest.height)
+        .flat_map(|y| (0..forest.width).map(move |x| (x, y)))
+        .filter(|position| forest.step(*position, direction).is_none());
+
+    // Each entry is the distance from the edge and the height of a tree which may block the view
+    let mut blockers: Vec<(usize, u8)> = Vec::new();
+    for start in starts {
+        blockers.clear();
+        let mut position = Some(start);
+        let mut distance_from_edge = 0;
+        while let Some((x, y)) = position {
+            let index = forest.index(x, y);
+            let tree = forest.trees[index];
+            while blockers.last().is_some_and(|(_, height)| *height < tree) {
+                blockers.pop();
+            }
+
+            match blockers.last() {
+                Some((blocker_distance, _)) => {
+                    viewing_distance[index] = distance_from_edge - blocker_distance;
+                }
+                None => {
+                    visible[index] = true;
+                    viewing_distance[index] = distance_from_edge;
+                }
+            }
+
+            blockers.push((distance_from_edge, tree));
+            distance_from_edge += 1;
+            position = forest.step((x, y), backwards);
+        }
+    }
+
+    DirectionView {
+        visible,
+        viewing_distance,
+    }
 }
@@ -1,7 +1,7 @@
 use clap::Parser;
 use std::{
-    collections::HashSet,
-    fs::File,
+    collections::{HashSet, VecDeque},
+    fs::{self, File},
     io::{BufRead, BufReader},
 };
 
@@ -12,11 +12,161 @@ struct Args {
     data_file: String,
     #[arg(long)]
     knots: usize,
+    /// Draw the positions visited by each knot
+    #[arg(long)]
+    render: bool,
+    /// Write a PPM of the positions visited by each knot to <prefix>-<knot>.ppm
+    #[arg(long)]
+    image_prefix: Option<String>,
+    /// How each knot follows the one in front of it, either standard, rigid or delayed
+    #[arg(long, default_value = "standard")]
+    follow: String,
+    /// Number of steps the delayed follow rule lags behind by
+    #[arg(long, default_value_t = 1)]
+    delay: usize,
+}
+
+// Decides where a knot moves to after the knot in front of it has moved
+trait FollowRule {
+    fn follow(
+        &mut self,
+        knot: usize,
+        leader_previous: (i32, i32),
+        leader: (i32, i32),
+        follower: (i32, i32),
+    ) -> (i32, i32);
+}
+
+// If the knot is no longer touching it moves one step towards the leader, diagonally if needed
+struct StandardFollow;
+
+impl FollowRule for StandardFollow {
+    fn follow(
+        &mut self,
+        _knot: usize,
+        _leader_previous: (i32, i32),
+        leader: (i32, i32),
+        follower: (i32, i32),
+    ) -> (i32, i32) {
+        step_towards(leader, follower)
+    }
+}
+
+// If the knot is no longer touching it moves to where the leader just was, like a chain
+struct RigidFollow;
+
+impl FollowRule for RigidFollow {
+    fn follow(
+        &mut self,
+        _knot: usize,
+        leader_previous: (i32, i32),
+        leader: (i32, i32),
+        follower: (i32, i32),
+    ) -> (i32, i32) {
+        if is_touching(leader, follower) {
+            return follower;
+        }
+
+        leader_previous
+    }
+}
+
+// Follows the standard rule but towards where the leader was `delay` steps ago
+struct DelayedFollow {
+    delay: usize,
+    // Recent positions of the leader of each knot, oldest first
+    history: Vec<VecDeque<(i32, i32)>>,
+}
+
+impl FollowRule for DelayedFollow {
+    fn follow(
+        &mut self,
+        knot: usize,
+        _leader_previous: (i32, i32),
+        leader: (i32, i32),
+        follower: (i32, i32),
+    ) -> (i32, i32) {
+        if self.history.len() <= knot {
+            self.history.resize_with(knot + 1, VecDeque::new);
+        }
+
+        let history = &mut self.history[knot];
+        history.push_back(leader);
+        if history.len() <= self.delay {
+            return follower;
+        }
+
+        let delayed_leader = history.pop_front().unwrap();
+        step_towards(delayed_leader, follower)
+    }
+}
+
+fn is_touching(leader: (i32, i32), follower: (i32, i32)) -> bool {
+    (leader.0 - follower.0).abs() <= 1 && (leader.1 - follower.1).abs() <= 1
+}
+
+fn step_towards(leader: (i32, i32), follower: (i32, i32)) -> (i32, i32) {
+    if is_touching(leader, follower) {
+        return follower;
+    }
+
+    (
+        follower.0 + (leader.0 - follower.0).signum(),
+        follower.1 + (leader.1 - follower.1).signum(),
+    )
+}
+
+// The knots of the rope with the head first and the tail last
+struct Rope {
+    knots: Vec<(i32, i32)>,
+    rule: Box<dyn FollowRule>,
+}
+
+impl Rope {
+    fn new(knot_count: usize, rule: Box<dyn FollowRule>) -> Rope {
+        Rope {
+            knots: vec![(0, 0); knot_count],
+            rule,
+        }
+    }
+
+    fn knots(&self) -> &[(i32, i32)] {
+        &self.knots
+    }
+
+    // Moves the head one step in `direction` and lets the rest of the rope follow
+    fn step(&mut self, direction: (i32, i32)) {
+        let mut leader_previous = self.knots[0];
+        self.knots[0].0 += direction.0;
+        self.knots[0].1 += direction.1;
+
+        for knot in 1..self.knots.len() {
+            let previous = self.knots[knot];
+            self.knots[knot] =
+                self.rule
+                    .follow(knot, leader_previous, self.knots[knot - 1], previous);
+            leader_previous = previous;
+        }
+    }
 }
 
 fn main() {
     let args = Args::parse();
 
+    if args.knots == 0 {
+        panic!("The rope needs at least one knot");
+    }
+
+    let rule: Box<dyn FollowRule> = match args.follow.as_str() {
+        "standard" => Box::new(StandardFollow),
+        "rigid" => Box::new(RigidFollow),
+        "delayed" => Box::new(DelayedFollow {
+            delay: args.delay,
+            history: Vec::new(),
+        }),
+        _ => panic!("Unknown follow rule: {}", args.follow),
+    };
+
     let file = File::open(&args.data_file).expect("Failed to open file");
     let reader = BufReader::new(file);
 
@@ -25,70 +175,126 @@ fn main() {
         .map(|line| line.expect("Failed to parse line"))
         .collect();
 
-    let initial_state = vec![(0, 0); args.knots];
-
-    let tail_covered_positions: Vec<(i32, i32)> = lines
-        .iter()
-        .scan(initial_state, |state, instruction| {
-            let (direction, count) = instruction.split_once(' ').unwrap();
-            let change = match direction {
-                "R" => (1, 0),
-                "U" => (0, 1),
-                "D" => (0, -1),
-                "L" => (-1, 0),
-                _ => panic!("Unexpected direction"),
-            };
-            let knot_count = state.len();
-
-            let mut tail_covered_states = Vec::new();
-            for _ in 0..(count.parse().expect("Failed to parse count")) {
-                state[knot_count - 1].0 += change.0;
-                state[knot_count - 1].1 += change.1;
-
-                for knot in (0..(state.len() - 1)).rev() {
-                    if ((state[knot].0 - state[knot + 1].0) as i32).abs() > 1
-                        || ((state[knot].1 - state[knot + 1].1) as i32).abs() > 1
-                    {
-                        // Tail needs to move
-                        if state[knot].1 != state[knot + 1].1 {
-                            state[knot].1 +=
-                                if ((state[knot + 1].1 - state[knot].1) as i32).is_negative() {
-                                    -1
-                                } else {
-                                    1
-                                };
-                        }
-
-                        if state[knot].0 != state[knot + 1].0 {
-                            state[knot].0 +=
-                                if ((state[knot + 1].0 - state[knot].0) as i32).is_negative() {
-                                    -1
-                                } else {
-                                    1
-                                };
-                        }
-                    }
-
-                    if knot == 0 {
-                        tail_covered_states.push(state[0]);
-                    }
-                }
+    let mut rope = Rope::new(args.knots, rule);
+    let knot_count = rope.knots().len();
+    let mut visited: Vec<HashSet<(i32, i32)>> = vec![HashSet::from([(0, 0)]); knot_count];
+
+    for instruction in lines.iter() {
+        let (direction, count) = instruction.split_once(' ').unwrap();
+        let direction = parse_direction(direction);
+
+        for _ in 0..(count.parse().expect("Failed to parse count")) {
+            rope.step(direction);
+            for (knot, position) in rope.knots().iter().enumerate() {
+                visited[knot].insert(*position);
             }
+        }
+    }
 
-            Some(tail_covered_states)
-        })
-        .flat_map(|s| s)
-        .collect();
+    // Report the knots from the head back to the tail, labelled as in the puzzle
+    println!("Knot  Unique positions");
+    for (knot, trail) in visited.iter().enumerate() {
+        println!("{:>4}  {}", knot_label(knot), trail.len());
+    }
 
-    tail_covered_positions
-        .iter()
-        .for_each(|p| println!("({},{})", p.0, p.1));
+    if args.render {
+        for (knot, trail) in visited.iter().enumerate() {
+            println!();
+            println!("Knot {}:", knot_label(knot));
+            print_trail(trail);
+        }
+    }
 
-    let tail_unique_positions: HashSet<(i32, i32)> =
-        HashSet::from_iter(tail_covered_positions.iter().cloned());
+    if let Some(image_prefix) = args.image_prefix {
+        for (knot, trail) in visited.iter().enumerate() {
+            write_trail_image(trail, &format!("{}-{}.ppm", image_prefix, knot_label(knot)));
+        }
+    }
 
     println!(
         "Number of positions covered: {}",
-        tail_unique_positions.len()
+        visited.last().unwrap().len()
     );
 }
+
+// Directions are R, L, U and D, or a diagonal such as UR or DL
+fn parse_direction(direction: &str) -> (i32, i32) {
+    let mut change = (0, 0);
+    for c in direction.chars() {
+        match c {
+            'R' if change.0 == 0 => change.0 = 1,
+            'L' if change.0 == 0 => change.0 = -1,
+            'U' if change.1 == 0 => change.1 = 1,
+            'D' if change.1 == 0 => change.1 = -1,
+            _ => panic!("Unexpected direction: {}", direction),
+        }
+    }
+
+    if change == (0, 0) {
+        panic!("Unexpected direction: {}", direction);
+    }
+
+    change
+}
+
+fn knot_label(knot: usize) -> String {
+    if knot == 0 {
+        return "H".to_string();
+    }
+
+    knot.to_string()
+}
+
+// Returns the smallest and largest x and y of the trail
+fn trail_bounds(trail: &HashSet<(i32, i32)>) -> ((i32, i32), (i32, i32)) {
+    let min_x = trail.iter().map(|p| p.0).min().unwrap();
+    let max_x = trail.iter().map(|p| p.0).max().unwrap();
+    let min_y = trail.iter().map(|p| p.1).min().unwrap();
+    let max_y = trail.iter().map(|p| p.1).max().unwrap();
+
+    ((min_x, min_y), (max_x, max_y))
+}
+
+// Draws the trail like the puzzle's diagrams, with s for the start and # for each visited position
+fn print_trail(trail: &HashSet<(i32, i32)>) {
+    let ((min_x, min_y), (max_x, max_y)) = trail_bounds(trail);
+
+    // Up is positive y so draw the rows from the top down
+    for y in (min_y..=max_y).rev() {
+        let row: String = (min_x..=max_x)
+            .map(|x| {
+                if (x, y) == (0, 0) {
+                    's'
+                } else if trail.contains(&(x, y)) {
+                    '#'
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        println!("{}", row);
+    }
+}
+
+// Writes a binary PPM with one pixel per position, white for visited and red for the start
+fn write_trail_image(trail: &HashSet<(i32, i32)>, path: &str) {
+    let ((min_x, min_y), (max_x, max_y)) = trail_bounds(trail);
+    let width = max_x - min_x + 1;
+    let height = max_y - min_y + 1;
+
+    let mut image = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    for y in (min_y..=max_y).rev() {
+        for x in min_x..=max_x {
+            let pixel: [u8; 3] = if (x, y) == (0, 0) {
+                [255, 0, 0]
+            } else if trail.contains(&(x, y)) {
+                [255, 255, 255]
+            } else {
+                [0, 0, 0]
+            };
+            image.extend_from_slice(&pixel);
+        }
+    }
+
+    fs::write(path, image).expect("Failed to write image");
+}